@@ -1,6 +1,12 @@
+use std::env;
 use std::process::Command;
 
 fn main() {
+    // Die Resourcen braucht nur die GUI, ohne `gui` Feature wird kein GTK benötigt
+    if env::var("CARGO_FEATURE_GUI").is_err() {
+        return;
+    }
+
     Command::new("glib-compile-resources")
         .args(&["--generate", "resources.xml"])
         .current_dir("src/gui/gtk3/resources")
@@ -0,0 +1,72 @@
+//! Misst wie viele Shift-Outs pro Sekunde möglich sind
+//!
+//! Verglichen wird das alte Verhalten, bei dem vor jedem Shift-Out die Pins neu exportiert und
+//! konfiguriert wurden, mit einer offenen GPIO Session.
+//!
+//! Aussagekräftig ist die Messung nur über das `SysfsBackend`, nur dort kosten Export und
+//! Richtung der Pins tatsächlich Schreibzugriffe im sysfs. Dafür braucht es die xMZ-Mod-Touch
+//! (LED Pins aus dem eingebauten Profil) oder das `gpio-mockup` Modul des Kernels, bei dem die
+//! Pins OE, DS, CLOCK und LATCH ab der angegebenen Basis Nummer liegen.
+//!
+//! ```text
+//! cargo run --release --example shift_out_benchmark                # LED Pins der xMZ-Mod-Touch
+//!
+//! sudo modprobe gpio-mockup gpio_mockup_ranges=-1,4
+//! cargo run --release --example shift_out_benchmark -- sysfs 508   # gpio-mockup ab Pin 508
+//!
+//! cargo run --release --example shift_out_benchmark -- recording   # ohne Hardware
+//! ```
+//!
+//! Mit `recording` laufen beide Messungen im Speicher über das `RecordingBackend`. Das zeigt nur
+//! den Aufwand im Programm selbst und ist **nicht repräsentativ** für die Hardware.
+extern crate xmz_mod_touch_test_tool;
+
+use std::env;
+use std::time::{Duration, Instant};
+use xmz_mod_touch_test_tool::shift_register::*;
+
+const ITERATIONS: u32 = 200;
+
+
+fn per_second(duration: Duration) -> f64 {
+    let seconds = duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000.0;
+    ITERATIONS as f64 / seconds
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let recording = args.get(0).map(|backend| backend == "recording").unwrap_or(false);
+    let mut leds = match recording {
+        true => ShiftRegister::with_backend(ShiftRegisterType::LED, Box::new(RecordingBackend::new())),
+        false => ShiftRegister::with_backend(ShiftRegisterType::LED, Box::new(SysfsBackend::new())),
+    };
+    if let Some(base) = args.get(1).and_then(|base| base.parse::<u64>().ok()) {
+        leds.oe_pin = Some(base);
+        leds.ds_pin = Some(base + 1);
+        leds.clock_pin = Some(base + 2);
+        leds.latch_pin = Some(base + 3);
+    }
+
+    // Vorher: Pins werden bei jedem Shift-Out neu exportiert und konfiguriert
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        leds.close_session().unwrap();
+        leds.toggle(1).unwrap();
+    }
+    let before = per_second(start.elapsed());
+
+    // Nachher: die Session bleibt offen
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        leds.toggle(1).unwrap();
+    }
+    let after = per_second(start.elapsed());
+
+    leds.reset().unwrap();
+
+    if recording {
+        println!("RecordingBackend im Speicher, die Werte sind nicht repräsentativ für die Hardware!");
+    }
+    println!("Shift-Outs pro Sekunde, Pins jedes Mal neu exportiert: {:.1}", before);
+    println!("Shift-Outs pro Sekunde, offene GPIO Session:           {:.1}", after);
+}
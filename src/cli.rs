@@ -0,0 +1,564 @@
+//! Kommandozeile ohne GUI
+//!
+//! Für die Wartung über SSH und für Prüfskripte lassen sich LED und Relais auch ohne GTK
+//! schalten. Ohne Argumente startet `xmz-mod-touch-test-tool` die GUI, ansonsten wird das
+//! Kommando ausgeführt, siehe `USAGE`. Mit `--json` wird das Ergebnis bzw. der Fehler als JSON auf
+//! stdout ausgegeben.
+//!
+//! Jeder Aufruf ist ein eigener Prozess. Damit z.B. `relais set 3` die übrigen Relais nicht wieder
+//! ausschaltet, wird der Stand der Ausgänge nach jedem Kommando in der State Datei gespeichert
+//! und beim nächsten Aufruf wieder geladen. Am Ende werden die Pins freigegeben ohne die Ausgänge
+//! in den sicheren Zustand zu bringen, die Ausgänge bleiben also geschaltet. Nur nach einem Fehler
+//! der Hardware (`Severity::Error`) gehen LED und Relais in den sicheren Zustand.
+//!
+//! Geht ein Shift Register in den sicheren Zustand, z.B. bei SIGINT oder beim Beenden der GUI, wird
+//! die State Datei gelöscht (`safe_state::register_state_file()`). Das nächste Kommando beginnt
+//! dann mit allen Ausgängen aus, anstatt einen alten Stand wieder einzuschalten.
+//!
+//! # Examples
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::cli::*;
+//! use xmz_mod_touch_test_tool::profile::HardwareProfile;
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let profile = || HardwareProfile {
+//!     leds: ShiftRegister::new(ShiftRegisterType::Simulation),
+//!     relais: ShiftRegister::new(ShiftRegisterType::Simulation),
+//!     modbus: None,
+//! };
+//! let state = ::std::env::temp_dir().join("xmz-cli-doctest.state.json");
+//! let _ = ::std::fs::remove_file(&state);
+//!
+//! let cli = Cli::parse(&["relais", "set", "3"]).unwrap();
+//! assert_eq!(cli.command, Command::Set(Register::Relais, 3));
+//! run(&cli, profile(), Some(&state)).unwrap();
+//!
+//! // Der nächste Aufruf kennt den Stand des vorherigen
+//! let cli = Cli::parse(&["--json", "relais", "set", "1"]).unwrap();
+//! let report = run(&cli, profile(), Some(&state)).unwrap();
+//! let on: Vec<u64> = report.relais.iter().filter(|output| output.on).map(|output| output.num).collect();
+//! assert_eq!(on, vec![1, 3]);
+//! assert!(report.to_json().unwrap().contains("\"ok\": true"));
+//!
+//! let cli = Cli::parse(&["leds", "pattern", "walking-one", "0"]).unwrap();
+//! assert_eq!(cli.command, Command::Pattern(Register::Leds, Pattern::Diagnostic(DiagnosticPattern::WalkingOne), 0));
+//! run(&cli, profile(), Some(&state)).unwrap();
+//!
+//! let err = Cli::parse(&["relais", "fly"]).unwrap_err();
+//! assert_eq!(exit_code(&err), EXIT_USAGE);
+//! let err = run(&Cli::parse(&["relais", "set", "99"]).unwrap(), profile(), Some(&state)).unwrap_err();
+//! assert_eq!(exit_code(&err), EXIT_REJECTED);
+//! ```
+use errors::*;
+use profile::HardwareProfile;
+use rand;
+use sequence::{ModbusBus, Operator, RtuBus, Sequence, SequenceReport};
+use serde_json;
+use shift_register::*;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+
+/// Pfad der State Datei, wenn keiner angegeben wurde
+///
+/// Nach einem Neustart sind alle Ausgänge aus, deshalb liegt die Datei unter `/run`. Anders als
+/// im `/tmp` kann dort kein anderer Benutzer die Datei vorgeben oder umlenken.
+pub const DEFAULT_STATE_PATH: &'static str = "/run/xmz-mod-touch-test-tool/state.json";
+
+/// Kommando erfolgreich ausgeführt
+pub const EXIT_OK: i32 = 0;
+/// Fehler der Hardware, des Hardware Profils oder der Kommunikation
+pub const EXIT_ERROR: i32 = 1;
+/// Unbekanntes Kommando oder falsche Argumente
+pub const EXIT_USAGE: i32 = 2;
+/// Das Kommando wurde abgelehnt, z.B. wegen einer Verriegelung oder einer ungültigen Ausgangsnummer
+pub const EXIT_REJECTED: i32 = 3;
+/// Die Prüfsequenz wurde nicht bestanden oder abgebrochen
+pub const EXIT_FAILED: i32 = 4;
+
+/// Anzeigedauer eines Frames der Muster, wenn keine angegeben wurde
+const PATTERN_INTERVAL_MS: u64 = 200;
+/// Abfrage Intervall für das Ende des Musters
+const PATTERN_POLL_MS: u64 = 10;
+
+/// Hilfe Text der Kommandozeile
+pub const USAGE: &'static str = "\
+Aufruf: xmz-mod-touch-test-tool [--json] [KOMMANDO]
+
+Ohne Kommando wird die GUI gestartet.
+
+Kommandos:
+    relais|leds set NUM             Ausgang NUM einschalten
+    relais|leds clear NUM           Ausgang NUM ausschalten
+    relais|leds toggle NUM          Ausgang NUM umschalten
+    relais|leds all                 Alle Ausgänge einschalten, außer verriegelte
+    relais|leds reset               Alle Ausgänge ausschalten
+    relais|leds pattern MUSTER [MS] Muster einmal abspielen, MS pro Frame (Standard 200)
+                                    MUSTER: chase, knight-rider, blink, fill, walking-one,
+                                    walking-zero, checkerboard, inverse-checkerboard,
+                                    address-lines
+    relais|leds random [SEED]       Zufallstest, mit SEED wird ein früherer Test wiederholt
+    sequence DATEI                  Prüfsequenz ausführen, Fragen werden auf stdin beantwortet
+    state                           Stand aller Ausgänge ausgeben
+    reset                           Alle LED und Relais ausschalten
+    help                            Diesen Text ausgeben
+
+Optionen:
+    --json                          Ergebnis und Fehler als JSON ausgeben
+
+Exit Codes:
+    0   Erfolg
+    1   Fehler der Hardware, des Hardware Profils oder der Kommunikation
+    2   Unbekanntes Kommando oder falsche Argumente
+    3   Kommando abgelehnt, z.B. Verriegelung oder ungültige Ausgangsnummer
+    4   Prüfsequenz nicht bestanden oder abgebrochen
+";
+
+pub use profile::Register;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Set(Register, u64),
+    Clear(Register, u64),
+    Toggle(Register, u64),
+    All(Register),
+    /// `None` schaltet LED und Relais aus
+    Reset(Option<Register>),
+    /// Muster und Anzeigedauer eines Frames in Millisekunden
+    Pattern(Register, Pattern, u64),
+    /// Seed eines früheren Tests, `None` erzeugt einen neuen Seed
+    Random(Register, Option<u64>),
+    /// Pfad der Prüfsequenz
+    Sequence(PathBuf),
+    State,
+    Help,
+}
+
+/// Geparste Kommandozeile
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cli {
+    pub command: Command,
+    /// Ausgabe als JSON statt als Text
+    pub json: bool,
+}
+
+impl Cli {
+    /// Parst die Argumente, ohne den Programmnamen
+    ///
+    /// Fehler haben immer die Art `ErrorKind::InvalidArguments`.
+    pub fn parse<S: AsRef<str>>(args: &[S]) -> Result<Self> {
+        let mut json = false;
+        let mut words = vec![];
+        for arg in args {
+            match arg.as_ref() {
+                "--json" => json = true,
+                "-h" | "--help" => words.push("help"),
+                word if word.starts_with('-') => bail!(usage(format!("unbekannte Option {}", word))),
+                word => words.push(word),
+            }
+        }
+
+        Ok(Cli {
+            command: try!(parse_command(&words)),
+            json: json,
+        })
+    }
+}
+
+/// Stand eines Ausgangs
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize)]
+pub struct Output {
+    pub num: u64,
+    pub label: String,
+    pub on: bool,
+}
+
+/// Ergebnis eines Kommandos, der Stand aller Ausgänge danach
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize)]
+pub struct Report {
+    pub relais: Vec<Output>,
+    pub leds: Vec<Output>,
+    /// Nur beim `random` Kommando, damit der Test wiederholt werden kann
+    pub random_test: Option<RandomTest>,
+    /// Nur beim `sequence` Kommando
+    pub sequence: Option<SequenceReport>,
+}
+
+impl Report {
+    fn new(relais: &ShiftRegister, leds: &ShiftRegister, details: Details) -> Self {
+        Report {
+            relais: outputs(relais),
+            leds: outputs(leds),
+            random_test: details.random_test,
+            sequence: details.sequence,
+        }
+    }
+
+    /// `EXIT_OK`, bzw. `EXIT_FAILED` wenn die Prüfsequenz nicht bestanden wurde
+    pub fn exit_code(&self) -> i32 {
+        match self.sequence {
+            Some(ref sequence) if !sequence.passed => EXIT_FAILED,
+            _ => EXIT_OK,
+        }
+    }
+
+    /// Ergebnis als JSON, mit `"ok": true`
+    pub fn to_json(&self) -> Result<String> {
+        #[derive(Serialize)]
+        struct Json<'a> {
+            ok: bool,
+            relais: &'a [Output],
+            leds: &'a [Output],
+            random_test: &'a Option<RandomTest>,
+            sequence: &'a Option<SequenceReport>,
+        }
+
+        Ok(try!(serde_json::to_string_pretty(&Json {
+            ok: true,
+            relais: &self.relais,
+            leds: &self.leds,
+            random_test: &self.random_test,
+            sequence: &self.sequence,
+        })))
+    }
+}
+
+impl fmt::Display for Report {
+    /// Eine Zeile pro Shift Register mit den eingeschalteten Ausgängen
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &(name, ref outputs) in &[("Relais", &self.relais), ("LED", &self.leds)] {
+            let on: Vec<String> = outputs.iter()
+                .filter(|output| output.on)
+                .map(|output| format!("{} ({})", output.label, output.num))
+                .collect();
+            match on.is_empty() {
+                true => try!(writeln!(f, "{}: alle aus", name)),
+                false => try!(writeln!(f, "{} an: {}", name, on.join(", "))),
+            }
+        }
+        if let Some(ref random_test) = self.random_test {
+            try!(writeln!(f, "{}", random_test));
+        }
+        if let Some(ref sequence) = self.sequence {
+            try!(write!(f, "{}", sequence));
+        }
+
+        Ok(())
+    }
+}
+
+/// Fehler als JSON, mit `"ok": false`, Ursachen, Schwere und Exit Code
+pub fn error_json(err: &Error) -> Result<String> {
+    #[derive(Serialize)]
+    struct Json {
+        ok: bool,
+        error: String,
+        causes: Vec<String>,
+        severity: Severity,
+        exit_code: i32,
+    }
+
+    Ok(try!(serde_json::to_string_pretty(&Json {
+        ok: false,
+        error: err.to_string(),
+        causes: err.iter().skip(1).map(|cause| cause.to_string()).collect(),
+        severity: err.severity(),
+        exit_code: exit_code(err),
+    })))
+}
+
+/// Exit Code zum Fehler, siehe `EXIT_ERROR`, `EXIT_USAGE` und `EXIT_REJECTED`
+///
+/// Eine abweichende Rückmeldung ist ein Fehler der Hardware, auch wenn das Kommando ausgeführt
+/// wurde.
+pub fn exit_code(err: &Error) -> i32 {
+    match (err.kind(), err.severity()) {
+        (&ErrorKind::InvalidArguments(..), _) => EXIT_USAGE,
+        (&ErrorKind::ReadbackMismatch(..), _) => EXIT_ERROR,
+        (_, Severity::Warning) => EXIT_REJECTED,
+        (_, Severity::Error) => EXIT_ERROR,
+    }
+}
+
+/// Führt das Kommando aus
+///
+/// # Arguments
+/// * `cli`         - Geparste Kommandozeile, `Command::Help` macht nix
+/// * `profile`     - Hardware Profil mit LED und Relais
+/// * `state_path`  - State Datei, `None` beginnt jedes Mal mit allen Ausgängen aus
+pub fn run(cli: &Cli, profile: HardwareProfile, state_path: Option<&Path>) -> Result<Report> {
+    let mut modbus = profile.modbus.map(|config| Box::new(RtuBus::new(config)) as Box<ModbusBus + Send>);
+    let relais = Arc::new(Mutex::new(profile.relais));
+    let leds = Arc::new(Mutex::new(profile.leds));
+    // Z.B. bei SIGINT während eines Musters
+    safe_state::register(&relais);
+    safe_state::register(&leds);
+
+    if let Some(path) = state_path {
+        try!(load_state(path, &relais, &leds)
+            .chain_err(|| format!("State Datei {} konnte nicht geladen werden", path.display())));
+        safe_state::register_state_file(path);
+    }
+
+    let result = execute(&cli.command, &relais, &leds, &mut modbus);
+    if let Err(ref err) = result {
+        // Nach einem Fehler der Hardware nicht mit halb geschalteten Ausgängen stehen bleiben,
+        // ein abgelehntes Kommando hat dagegen nix geändert
+        if err.severity() == Severity::Error {
+            let _ = relais.lock().unwrap().enter_safe_state();
+            let _ = leds.lock().unwrap().enter_safe_state();
+        }
+    }
+
+    let saved = match state_path {
+        Some(path) => save_state(path, &relais, &leds)
+            .chain_err(|| format!("State Datei {} konnte nicht gespeichert werden", path.display())),
+        None => Ok(()),
+    };
+    // Pins freigeben, ohne dass der `Drop` die Ausgänge in den sicheren Zustand bringt. Das gilt
+    // auch wenn die State Datei nicht gespeichert werden konnte, geliefert wird der erste Fehler.
+    let relais_closed = relais.lock().unwrap().close_session();
+    let leds_closed = leds.lock().unwrap().close_session();
+    try!(relais_closed.and(leds_closed).and(saved));
+
+    let details = try!(result);
+    let report = Report::new(&relais.lock().unwrap(), &leds.lock().unwrap(), details);
+
+    Ok(report)
+}
+
+fn usage(reason: String) -> ErrorKind {
+    ErrorKind::InvalidArguments(reason)
+}
+
+fn parse_command(words: &[&str]) -> Result<Command> {
+    let (first, rest) = match words.split_first() {
+        Some((first, rest)) => (*first, rest),
+        None => bail!(usage("kein Kommando angegeben".to_string())),
+    };
+    let register = match first {
+        "help" => return expect_arguments(first, rest, 0, 0).map(|_| Command::Help),
+        "state" => return expect_arguments(first, rest, 0, 0).map(|_| Command::State),
+        "reset" => return expect_arguments(first, rest, 0, 0).map(|_| Command::Reset(None)),
+        "sequence" => return expect_arguments(first, rest, 1, 1).map(|arguments| Command::Sequence(PathBuf::from(arguments[0]))),
+        "relais" => Register::Relais,
+        "leds" => Register::Leds,
+        _ => bail!(usage(format!("unbekanntes Kommando {}", first))),
+    };
+    let (action, arguments) = match rest.split_first() {
+        Some((action, arguments)) => (*action, arguments),
+        None => bail!(usage(format!("{}: keine Aktion angegeben", first))),
+    };
+
+    let command = match action {
+        "set" => Command::Set(register, try!(parse_number(action, try!(expect_arguments(action, arguments, 1, 1))[0]))),
+        "clear" => Command::Clear(register, try!(parse_number(action, try!(expect_arguments(action, arguments, 1, 1))[0]))),
+        "toggle" => Command::Toggle(register, try!(parse_number(action, try!(expect_arguments(action, arguments, 1, 1))[0]))),
+        "all" => try!(expect_arguments(action, arguments, 0, 0).map(|_| Command::All(register))),
+        "reset" => try!(expect_arguments(action, arguments, 0, 0).map(|_| Command::Reset(Some(register)))),
+        "pattern" => {
+            let arguments = try!(expect_arguments(action, arguments, 1, 2));
+            let interval = match arguments.get(1) {
+                Some(interval) => try!(parse_number(action, interval)),
+                None => PATTERN_INTERVAL_MS,
+            };
+            Command::Pattern(register, try!(parse_pattern(arguments[0])), interval)
+        },
+        "random" => {
+            let arguments = try!(expect_arguments(action, arguments, 0, 1));
+            match arguments.get(0) {
+                Some(seed) => Command::Random(register, Some(try!(parse_number(action, seed)))),
+                None => Command::Random(register, None),
+            }
+        },
+        _ => bail!(usage(format!("{}: unbekannte Aktion {}", first, action))),
+    };
+
+    Ok(command)
+}
+
+/// Prüft die Anzahl der Argumente einer Aktion
+fn expect_arguments<'a>(action: &str, arguments: &'a [&'a str], min: usize, max: usize) -> Result<&'a [&'a str]> {
+    if arguments.len() < min || arguments.len() > max {
+        bail!(usage(match (min, max) {
+            (0, 0) => format!("{} erwartet keine Argumente", action),
+            (min, max) if min == max => format!("{} erwartet {} Argument(e)", action, min),
+            (min, max) => format!("{} erwartet {} bis {} Argumente", action, min, max),
+        }));
+    }
+
+    Ok(arguments)
+}
+
+fn parse_number(action: &str, number: &str) -> Result<u64> {
+    match number.parse() {
+        Ok(number) => Ok(number),
+        Err(_) => bail!(usage(format!("{}: {} ist keine Zahl", action, number))),
+    }
+}
+
+fn parse_pattern(name: &str) -> Result<Pattern> {
+    match name {
+        "chase" => Ok(Pattern::Chase),
+        "knight-rider" => Ok(Pattern::KnightRider),
+        "blink" => Ok(Pattern::Blink),
+        "fill" => Ok(Pattern::Fill),
+        "walking-one" => Ok(Pattern::Diagnostic(DiagnosticPattern::WalkingOne)),
+        "walking-zero" => Ok(Pattern::Diagnostic(DiagnosticPattern::WalkingZero)),
+        "checkerboard" => Ok(Pattern::Diagnostic(DiagnosticPattern::Checkerboard)),
+        "inverse-checkerboard" => Ok(Pattern::Diagnostic(DiagnosticPattern::InverseCheckerboard)),
+        "address-lines" => Ok(Pattern::Diagnostic(DiagnosticPattern::AddressLines)),
+        _ => bail!(usage(format!("unbekanntes Muster {}", name))),
+    }
+}
+
+/// Ergebnisse einzelner Kommandos, zusätzlich zum Stand der Ausgänge
+#[derive(Default)]
+struct Details {
+    random_test: Option<RandomTest>,
+    sequence: Option<SequenceReport>,
+}
+
+/// Bediener am Terminal, die Fragen gehen auf stderr damit stdout für `--json` frei bleibt
+struct TerminalOperator;
+
+impl Operator for TerminalOperator {
+    fn ask(&mut self, question: &str) -> bool {
+        let _ = write!(io::stderr(), "{} [j/n] ", question);
+        let mut answer = String::new();
+        let stdin = io::stdin();
+        if stdin.lock().read_line(&mut answer).is_err() {
+            return false;
+        }
+        // "ja" oder "yes"
+        let answer = answer.trim().to_lowercase();
+        answer.starts_with('j') || answer.starts_with('y')
+    }
+}
+
+/// Führt das Kommando aus, liefert die Details zum `random` und `sequence` Kommando
+fn execute(command: &Command, relais: &Arc<Mutex<ShiftRegister>>, leds: &Arc<Mutex<ShiftRegister>>,
+           modbus: &mut Option<Box<ModbusBus + Send>>) -> Result<Details> {
+    let select = |register: Register| match register {
+        Register::Relais => relais,
+        Register::Leds => leds,
+    };
+
+    match *command {
+        Command::Set(register, num) => try!(select(register).lock().unwrap().set(num)),
+        Command::Clear(register, num) => try!(select(register).lock().unwrap().clear(num)),
+        Command::Toggle(register, num) => try!(select(register).lock().unwrap().toggle(num)),
+        Command::All(register) => {
+            try!(select(register).lock().unwrap().all());
+        },
+        Command::Reset(Some(register)) => try!(select(register).lock().unwrap().reset()),
+        Command::Reset(None) => {
+            try!(relais.lock().unwrap().reset());
+            try!(leds.lock().unwrap().reset());
+        },
+        Command::Pattern(register, ref pattern, interval) => {
+            let runner = PatternRunner::start(select(register).clone(), pattern.clone(), Duration::from_millis(interval), false);
+            while !runner.is_finished() {
+                thread::sleep(Duration::from_millis(PATTERN_POLL_MS));
+            }
+            try!(runner.stop());
+        },
+        Command::Random(register, seed) => {
+            return Ok(Details {
+                random_test: Some(try!(select(register).lock().unwrap().test_random(seed))),
+                sequence: None,
+            });
+        },
+        Command::Sequence(ref path) => {
+            let sequence = try!(Sequence::load(path)
+                .chain_err(|| format!("Prüfsequenz {} konnte nicht geladen werden", path.display())));
+            return Ok(Details {
+                random_test: None,
+                sequence: Some(try!(sequence.run(relais, leds, modbus, &mut TerminalOperator))),
+            });
+        },
+        Command::State | Command::Help => {},
+    }
+
+    Ok(Details::default())
+}
+
+/// Ausgänge des Shift Registers, ein Eintrag pro vorhandenem Ausgang
+fn outputs(register: &ShiftRegister) -> Vec<Output> {
+    (1..register.outputs as u64 + 1)
+        .map(|num| {
+            Output {
+                num: num,
+                label: register.label(num),
+                on: register.get(num).unwrap_or(false),
+            }
+        })
+        .collect()
+}
+
+/// Inhalt der State Datei, jeweils im Aufbau von `ShiftRegister.data`
+#[derive(Serialize, Deserialize)]
+struct State {
+    relais: Vec<u8>,
+    leds: Vec<u8>,
+}
+
+/// Übernimmt den gespeicherten Stand, existiert die Datei noch nicht sind alle Ausgänge aus
+fn load_state(path: &Path, relais: &Arc<Mutex<ShiftRegister>>, leds: &Arc<Mutex<ShiftRegister>>) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let file = try!(File::open(path));
+    let state: State = try!(serde_json::from_reader(file));
+
+    for &(register, ref data) in &[(relais, &state.relais), (leds, &state.leds)] {
+        let mut register = register.lock().unwrap();
+        // Passt der Stand nicht zum Hardware Profil, wurde das Profil inzwischen geändert
+        match register.chips == data.len() {
+            true => register.data = data.to_vec(),
+            false => warn!("{:?}: gespeicherter Stand {:?} passt nicht zum Hardware Profil und wird verworfen",
+                           register.register_type(), data),
+        }
+    }
+
+    Ok(())
+}
+
+/// Speichert den Stand über eine temporäre Datei, die dann umbenannt wird
+///
+/// Die temporäre Datei bekommt einen zufälligen Namen und wird nur neu angelegt, eine vorhandene
+/// Datei oder ein Symlink unter diesem Namen wird nie beschrieben.
+fn save_state(path: &Path, relais: &Arc<Mutex<ShiftRegister>>, leds: &Arc<Mutex<ShiftRegister>>) -> Result<()> {
+    // Ein Byte pro Shift Register, auch wenn `data` noch nicht auf `chips` gebracht wurde
+    let data = |register: &Arc<Mutex<ShiftRegister>>| {
+        let register = register.lock().unwrap();
+        let mut data = register.data.clone();
+        data.resize(register.chips, 0);
+        data
+    };
+    let state = State {
+        relais: data(relais),
+        leds: data(leds),
+    };
+    if let Some(dir) = path.parent() {
+        try!(fs::create_dir_all(dir));
+    }
+    let tmp_path = path.with_extension(format!("{:08x}.tmp", rand::random::<u32>()));
+    let file = try!(OpenOptions::new().write(true).create_new(true).open(&tmp_path));
+    // Die temporäre Datei nicht liegen lassen, wenn Schreiben oder Umbenennen fehlschlägt
+    let saved = serde_json::to_writer(file, &state).map_err(Error::from)
+        .and_then(|_| fs::rename(&tmp_path, path).map_err(Error::from));
+    if saved.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    saved
+}
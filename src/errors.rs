@@ -2,8 +2,112 @@ error_chain!{
     foreign_links {
         Fmt(::std::fmt::Error);
         Gpio(::sysfs_gpio::Error) #[cfg(unix)];
+        GpioCdev(::gpio_cdev::Error) #[cfg(unix)];
         Io(::std::io::Error) #[cfg(unix)];
         SerdeJson(::serde_json::Error);
         Modbus(::libmodbus_rs::Error);
     }
+
+    errors {
+        InvalidArguments(reason: String) {
+            description("Ungültige Argumente")
+            display("Ungültige Argumente: {}", reason)
+        }
+        InvalidProfile(reason: String) {
+            description("Ungültiges Hardware Profil")
+            display("Ungültiges Hardware Profil: {}", reason)
+        }
+        InvalidSequence(reason: String) {
+            description("Ungültige Prüfsequenz")
+            display("Ungültige Prüfsequenz: {}", reason)
+        }
+        InvalidChannel(register: String, num: u64, outputs: usize) {
+            description("Ungültige Ausgangsnummer")
+            display("Ungültige Ausgangsnummer {} für {}, gültig sind 1 bis {}", num, register, outputs)
+        }
+        SpiLoopback(sent: Vec<u8>, received: Vec<u8>) {
+            description("SPI Loopback fehlgeschlagen")
+            display("SPI Loopback fehlgeschlagen, gesendet: {:?}, empfangen: {:?}", sent, received)
+        }
+        InterlockViolation(register: String, interlock: String, outputs: Vec<String>) {
+            description("Verriegelung verletzt")
+            display("{}: die Verriegelung \"{}\" verbietet dass {} gleichzeitig an sind",
+                    register, interlock, outputs.join(", "))
+        }
+        ReadbackMismatch(register: String, num: u64, label: String, expected: bool) {
+            description("Rückmeldung eines Ausgangs entspricht nicht dem Sollwert")
+            display("{} Ausgang {} ({}) sollte {} sein, die Rückmeldung ist {}", register, num, label,
+                    if *expected { "an" } else { "aus" }, if *expected { "aus" } else { "an" })
+        }
+    }
+}
+
+/// Schwere eines Fehlers, bestimmt die Darstellung in der GUI und das Log Level
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Serialize)]
+pub enum Severity {
+    /// Die Aktion wurde abgelehnt, z.B. wegen einer Verriegelung oder falscher Argumente auf der
+    /// Kommandozeile, oder sie wurde ausgeführt und nur die Rückmeldung passt nicht, siehe
+    /// `Error::applied()`
+    Warning,
+    /// Fehler der Hardware, des Hardware Profils oder der Kommunikation
+    Error,
+}
+
+impl Error {
+    /// Schwere des Fehlers
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xmz_mod_touch_test_tool::errors::*;
+    ///
+    /// let err: Error = ErrorKind::InvalidChannel("RELAIS".to_string(), 10, 9).into();
+    /// assert_eq!(err.severity(), Severity::Warning);
+    ///
+    /// let err: Error = ErrorKind::SpiLoopback(vec![1], vec![0]).into();
+    /// assert_eq!(err.severity(), Severity::Error);
+    /// ```
+    pub fn severity(&self) -> Severity {
+        match *self.kind() {
+            ErrorKind::InvalidArguments(..) |
+            ErrorKind::InvalidChannel(..) |
+            ErrorKind::InterlockViolation(..) |
+            ErrorKind::ReadbackMismatch(..) => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+
+    /// `true` wenn die Ausgänge trotz des Fehlers geschaltet wurden
+    ///
+    /// Bei `ErrorKind::ReadbackMismatch` liegt der neue Stand an den Ausgängen an, nur die
+    /// Rückmeldung weicht ab. Buttons dürfen dann nicht zurückgestellt werden, sonst zeigen sie
+    /// nicht mehr den Stand der Hardware.
+    pub fn applied(&self) -> bool {
+        match *self.kind() {
+            ErrorKind::ReadbackMismatch(..) => true,
+            _ => false,
+        }
+    }
+
+    /// Fehlermeldung zusammen mit allen Ursachen, eine Zeile pro Ursache
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xmz_mod_touch_test_tool::errors::*;
+    ///
+    /// let result: Result<()> = Err(ErrorKind::InvalidProfile("2 Relais".to_string()).into());
+    /// let err = result.chain_err(|| "Hardware Profil konnte nicht geladen werden").unwrap_err();
+    /// assert_eq!(err.chain_message(),
+    ///            "Hardware Profil konnte nicht geladen werden\nUrsache: Ungültiges Hardware Profil: 2 Relais");
+    /// ```
+    pub fn chain_message(&self) -> String {
+        let mut message = format!("{}", self);
+        for cause in self.iter().skip(1) {
+            message.push_str(&format!("\nUrsache: {}", cause));
+        }
+
+        message
+    }
 }
@@ -0,0 +1,98 @@
+use errors::*;
+use gtk;
+use gtk::prelude::*;
+use shift_register::*;
+use std::sync::{Arc, Mutex};
+
+
+/// Laufende Diagnose mit dem Shift Register auf dem sie läuft
+pub type DiagnosticRun = Arc<Mutex<Option<(Arc<Mutex<ShiftRegister>>, Diagnostic)>>>;
+
+/// Gibt den nächsten Schritt der Diagnose aus, ohne laufende Diagnose wird eine neue begonnen
+pub fn step(combo_register: &gtk::ComboBoxText, combo_pattern: &gtk::ComboBoxText, label: &gtk::Label,
+            relais: &Arc<Mutex<ShiftRegister>>, leds: &Arc<Mutex<ShiftRegister>>, run: &DiagnosticRun) -> Result<()> {
+    let mut run = run.lock().unwrap();
+    if run.is_none() {
+        let register = match combo_register.get_active_id().as_ref().map(|id| id.as_str()) {
+            Some("leds") => leds.clone(),
+            _ => relais.clone(),
+        };
+        let pattern = match combo_pattern.get_active_id().as_ref().map(|id| id.as_str()) {
+            Some("walking_zero") => DiagnosticPattern::WalkingZero,
+            Some("checkerboard") => DiagnosticPattern::Checkerboard,
+            Some("inverse_checkerboard") => DiagnosticPattern::InverseCheckerboard,
+            Some("address_lines") => DiagnosticPattern::AddressLines,
+            _ => DiagnosticPattern::WalkingOne,
+        };
+        let diagnostic = register.lock().unwrap().diagnostic(pattern);
+        *run = Some((register, diagnostic));
+    }
+
+    let finished = match *run {
+        Some((ref register, ref mut diagnostic)) => {
+            let mut register = register.lock().unwrap();
+            match diagnostic.step(&mut register)? {
+                Some(step) => {
+                    let mut text = format!("Schritt {} von {}, an: {}", step + 1, diagnostic.len(),
+                                           labels(&register, &diagnostic.active(step)));
+                    let skipped = diagnostic.skipped(step);
+                    if !skipped.is_empty() {
+                        text.push_str(&format!("\nWegen Verriegelung ausgelassen: {}", labels(&register, &skipped)));
+                    }
+                    label.set_text(&text);
+                    false
+                },
+                None => {
+                    register.reset()?;
+                    label.set_text(&summary(&register, diagnostic));
+                    true
+                },
+            }
+        },
+        None => true,
+    };
+    if finished {
+        *run = None;
+    }
+
+    Ok(())
+}
+
+/// Markiert den aktuellen Schritt der Diagnose als fehlerhaft
+pub fn flag(label: &gtk::Label, run: &DiagnosticRun) {
+    if let Some((_, ref mut diagnostic)) = *run.lock().unwrap() {
+        diagnostic.flag();
+        if let Some(step) = diagnostic.current() {
+            label.set_text(&format!("Schritt {} von {} als fehlerhaft markiert", step + 1, diagnostic.len()));
+        }
+    }
+}
+
+/// Verwirft die laufende Diagnose, z.B. wenn ein anderes Muster gewählt wurde
+pub fn cancel(label: &gtk::Label, run: &DiagnosticRun) {
+    if run.lock().unwrap().take().is_some() {
+        label.set_text("Diagnose abgebrochen");
+    }
+}
+
+fn labels(register: &ShiftRegister, nums: &[u64]) -> String {
+    match nums.is_empty() {
+        true => "keine".to_string(),
+        false => nums.iter().map(|num| register.label(*num)).collect::<Vec<String>>().join(", "),
+    }
+}
+
+fn summary(register: &ShiftRegister, diagnostic: &Diagnostic) -> String {
+    if diagnostic.flagged().is_empty() {
+        return format!("Diagnose {:?} ohne Fehler beendet", diagnostic.pattern());
+    }
+
+    let flagged: Vec<String> = diagnostic.flagged()
+        .iter()
+        .map(|flag| match flag.source {
+            FlagSource::Operator => format!("Schritt {} (Bediener), an: {}", flag.step + 1, labels(register, &flag.active)),
+            FlagSource::Readback => format!("Schritt {} (Rückmeldung), falsch: {}", flag.step + 1, labels(register, &flag.mismatched)),
+        })
+        .collect();
+    format!("Diagnose {:?} beendet, markiert:\n{}", diagnostic.pattern(), flagged.join("\n"))
+}
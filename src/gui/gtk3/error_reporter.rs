@@ -0,0 +1,79 @@
+//! Zentrale Fehlermeldung der GUI
+//!
+//! Alle Fehler der Controller laufen über den `ErrorReporter`. Er zeigt die Fehlermeldung samt
+//! Ursachen in der `info_bar`, die Farbe richtet sich nach `Error::severity()`. Zusätzlich wird
+//! der Fehler ins Log geschrieben, siehe `logger`.
+//!
+//! Schlägt die Aktion eines Toggle Buttons fehl, stellt `revert()` den Button zurück, außer die
+//! Ausgänge wurden trotzdem geschaltet (`Error::applied()`). Weil
+//! `set_active()` dabei wieder das `clicked` Signal auslöst, liefert `reverting()` solange `true`,
+//! die `clicked` Handler dürfen dann nichts tun.
+use errors::*;
+use gtk;
+use gtk::prelude::*;
+use std::cell::Cell;
+use std::rc::Rc;
+
+
+#[derive(Clone)]
+pub struct ErrorReporter {
+    info_bar: gtk::InfoBar,
+    label: gtk::Label,
+    reverting: Rc<Cell<bool>>,
+}
+
+impl ErrorReporter {
+    /// Der Schließen Button der `info_bar` versteckt sie wieder
+    pub fn new(info_bar: &gtk::InfoBar, label: &gtk::Label) -> Self {
+        info_bar.connect_response(|info_bar, _| info_bar.hide());
+
+        ErrorReporter {
+            info_bar: info_bar.clone(),
+            label: label.clone(),
+            reverting: Rc::new(Cell::new(false)),
+        }
+    }
+
+    /// Zeigt den Fehler in der `info_bar` und schreibt ihn ins Log
+    pub fn report(&self, err: &Error) {
+        let message = err.chain_message();
+        let message_type = match err.severity() {
+            Severity::Warning => {
+                warn!("{}", message.replace('\n', ", "));
+                gtk::MessageType::Warning
+            },
+            Severity::Error => {
+                error!("{}", message.replace('\n', ", "));
+                gtk::MessageType::Error
+            },
+        };
+
+        self.label.set_text(&message);
+        self.info_bar.set_message_type(message_type);
+        self.info_bar.show();
+    }
+
+    /// Wie `report()`, stellt danach den Toggle Button auf seinen vorherigen Stand zurück
+    ///
+    /// Wurden die Ausgänge trotz des Fehlers geschaltet, bleibt der Button wie er ist.
+    pub fn revert(&self, button: &gtk::ToggleButton, err: &Error) {
+        self.report(err);
+        if !err.applied() {
+            self.set_active(button, !button.get_active());
+        }
+    }
+
+    /// Setzt den Toggle Button ohne dass sein `clicked` Handler etwas tut
+    pub fn set_active(&self, button: &gtk::ToggleButton, active: bool) {
+        if button.get_active() != active {
+            self.reverting.set(true);
+            button.set_active(active);
+            self.reverting.set(false);
+        }
+    }
+
+    /// `true` solange ein Button zurückgestellt wird, siehe Modul Dokumentation
+    pub fn reverting(&self) -> bool {
+        self.reverting.get()
+    }
+}
@@ -0,0 +1,169 @@
+//! Virtuelle Front der xMZ-Mod-Touch für den Entwicklungsrechner
+//!
+//! Mit dem `development` Feature läuft die GUI ohne angeschlossene LED und Relais. Die virtuelle
+//! Front zeichnet die LED und Relais in einer `gtk::DrawingArea`, in der Anordnung und den Farben
+//! der echten Front. Die Front meldet sich mit `ShiftRegister::subscribe()` bei beiden Shift
+//! Registern an und wird nur nach einer Änderung neu gezeichnet. Farbe und Anordnung der LED kommen
+//! wie bei den Buttons aus dem Hardware Profil, siehe `ShiftRegister::colour()` und
+//! `ShiftRegister::grid_position()`.
+//!
+//! Bei `GpioDriver::Simulated` zeigt die Front die Speicherregister der virtuellen 74HC595 Kette,
+//! also das was tatsächlich ausgegeben wurde, ansonsten den `data` Buffer.
+extern crate cairo;
+extern crate glib;
+
+use gtk;
+use gtk::prelude::*;
+use shift_register::*;
+use std::cell::RefCell;
+use std::f64::consts::PI;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+
+/// Abfrage Intervall der Zustandsänderungen in Millisekunden
+const POLL_MS: u32 = 50;
+
+/// Stand der Ausgänge wie er an der Front zu sehen ist
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Snapshot {
+    leds: Vec<bool>,
+    /// Spalte, Zeile und Farbe jeder LED, `leds_layout[0]` gehört zu Led 1
+    leds_layout: Vec<(i32, i32, Colour)>,
+    /// Helligkeit der LED von `0.0` bis `1.0`
+    brightness: f64,
+    relais: Vec<bool>,
+    relais_labels: Vec<String>,
+}
+
+/// Hängt die virtuelle Front als eigenen Tab an das Notebook an
+pub fn setup(notebook: &gtk::Notebook, leds: &Arc<Mutex<ShiftRegister>>, relais: &Arc<Mutex<ShiftRegister>>) {
+    let area = gtk::DrawingArea::new();
+    let changes = vec![leds.lock().unwrap().subscribe(), relais.lock().unwrap().subscribe()];
+    let state: Rc<RefCell<Snapshot>> = Rc::new(RefCell::new(snapshot(leds, relais)));
+
+    area.connect_draw(clone!(state => move |area, cr| {
+        draw(area, cr, &state.borrow());
+        Inhibit(false)
+    }));
+
+    gtk::timeout_add(POLL_MS, clone!(area, state, leds, relais => move || {
+        let (changed, connected) = receive(&changes);
+        if changed {
+            let snapshot = snapshot(&leds, &relais);
+            if *state.borrow() != snapshot {
+                *state.borrow_mut() = snapshot;
+                area.queue_draw();
+            }
+        }
+        glib::Continue(connected)
+    }));
+
+    notebook.append_page(&area, Some(&gtk::Label::new(Some("Front"))));
+}
+
+/// Ausgänge des Shift Registers, ein Eintrag pro vorhandenem Ausgang
+fn outputs(register: &mut ShiftRegister) -> Vec<bool> {
+    let data = match register.simulated_chain() {
+        Some(chain) => chain.latches(),
+        None => register.data.clone(),
+    };
+    let enabled = register.outputs_enabled();
+
+    (0..register.outputs)
+        .map(|index| enabled && data.get(index / 8).map(|byte| byte & (1 << (index % 8)) != 0).unwrap_or(false))
+        .collect()
+}
+
+/// Leert die Empfänger, liefert ob sich etwas geändert hat und ob noch alle Shift Register da sind
+fn receive(changes: &[Receiver<Vec<u8>>]) -> (bool, bool) {
+    let (mut changed, mut connected) = (false, true);
+    for receiver in changes {
+        loop {
+            match receiver.try_recv() {
+                Ok(_) => changed = true,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    connected = false;
+                    break;
+                },
+            }
+        }
+    }
+
+    (changed, connected)
+}
+
+/// Liest den Stand beider Shift Register
+fn snapshot(leds: &Arc<Mutex<ShiftRegister>>, relais: &Arc<Mutex<ShiftRegister>>) -> Snapshot {
+    // Die Locks werden nacheinander genommen, nie beide gleichzeitig
+    let (leds, leds_layout, brightness) = {
+        let mut leds = leds.lock().unwrap();
+        let layout = (1..leds.outputs as u64 + 1)
+            .map(|num| {
+                let (column, row) = leds.grid_position(num);
+                // LED ohne Farbe im Hardware Profil werden weiß gezeichnet
+                (column, row, leds.colour(num).unwrap_or(Colour::White))
+            })
+            .collect();
+        (outputs(&mut leds), layout, f64::from(leds.brightness()) / 100.0)
+    };
+    let mut relais = relais.lock().unwrap();
+
+    Snapshot {
+        leds: leds,
+        leds_layout: leds_layout,
+        brightness: brightness,
+        relais: outputs(&mut relais),
+        relais_labels: (1..relais.outputs as u64 + 1).map(|num| relais.label(num)).collect(),
+    }
+}
+
+fn draw(area: &gtk::DrawingArea, cr: &cairo::Context, snapshot: &Snapshot) {
+    let width = area.get_allocated_width() as f64;
+    let height = area.get_allocated_height() as f64;
+    // Oben die LED, unten die Relais
+    let leds_height = height * 0.7;
+
+    // Gehäuse
+    cr.set_source_rgb(0.15, 0.15, 0.15);
+    cr.rectangle(0.0, 0.0, width, height);
+    cr.fill();
+
+    let columns = snapshot.leds_layout.iter().map(|&(column, _, _)| column + 1).max().unwrap_or(1) as f64;
+    let rows = snapshot.leds_layout.iter().map(|&(_, row, _)| row + 1).max().unwrap_or(1) as f64;
+    let radius = (width / columns).min(leds_height / rows) * 0.35;
+    for (index, &(column, row, colour)) in snapshot.leds_layout.iter().enumerate() {
+        let on = snapshot.leds.get(index).cloned().unwrap_or(false);
+        // Ausgeschaltete LED bleiben in ihrer Farbe angedeutet
+        let level = match on {
+            true => 0.25 + 0.75 * snapshot.brightness,
+            false => 0.15,
+        };
+        let (r, g, b) = colour.rgb();
+        cr.set_source_rgb(r * level, g * level, b * level);
+        cr.arc((column as f64 + 0.5) * width / columns, (row as f64 + 0.5) * leds_height / rows, radius, 0.0, 2.0 * PI);
+        cr.fill();
+    }
+
+    let slot = width / snapshot.relais.len().max(1) as f64;
+    let (relais_width, relais_height) = (slot * 0.7, (height - leds_height) * 0.5);
+    let top = leds_height + (height - leds_height) * 0.1;
+    cr.set_line_width(2.0);
+    cr.set_font_size(relais_height * 0.25);
+    for (index, on) in snapshot.relais.iter().enumerate() {
+        let left = index as f64 * slot + (slot - relais_width) / 2.0;
+        // Angezogene Relais ausgefüllt
+        cr.set_source_rgb(0.8, 0.8, 0.8);
+        cr.rectangle(left, top, relais_width, relais_height);
+        match *on {
+            true => cr.fill(),
+            false => cr.stroke(),
+        }
+        if let Some(label) = snapshot.relais_labels.get(index) {
+            cr.move_to(left, top + relais_height * 1.5);
+            cr.show_text(label);
+        }
+    }
+}
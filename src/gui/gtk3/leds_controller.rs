@@ -9,7 +9,9 @@ use std::sync::{Arc, Mutex};
 pub fn all(button: &gtk::ToggleButton, leds: &Arc<Mutex<ShiftRegister>>) -> Result<()> {
     let mut leds = leds.lock().unwrap();
     match button.get_active() {
-        true => leds.all()?,
+        true => {
+            leds.all()?;
+        },
         false => leds.reset()?,
     }
 
@@ -19,27 +21,35 @@ pub fn all(button: &gtk::ToggleButton, leds: &Arc<Mutex<ShiftRegister>>) -> Resu
 pub fn random(button: &gtk::ToggleButton, leds: &Arc<Mutex<ShiftRegister>>) -> Result<()> {
     let mut leds = leds.lock().unwrap();
     match button.get_active() {
-        true => leds.test_random()?,
+        true => {
+            // Seed anzeigen, damit ein Fehler mit dem selben Seed nachgestellt werden kann
+            let test = leds.test_random(None)?;
+            button.set_tooltip_text(Some(&format!("Seed {}", test.seed)));
+        },
         false => leds.reset()?,
     }
 
     Ok(())
 }
 
-pub fn one_after_one(button: &gtk::ToggleButton, leds: &Arc<Mutex<ShiftRegister>>) -> Result<()> {
-    let mut leds = leds.lock().unwrap();
+pub fn one_after_one(button: &gtk::ToggleButton, leds: &Arc<Mutex<ShiftRegister>>, runner: &Arc<Mutex<Option<PatternRunner>>>) -> Result<()> {
     match button.get_active() {
-        true => {
-            for i in 1..21 {
-                leds.set(i);
-
-                ::std::thread::sleep(::std::time::Duration::from_millis(100));
-            }
-        },
-        false => leds.reset()?,
+        true => ::gui::gtk3::pattern_controller::start(leds, runner, Pattern::Fill, false),
+        false => ::gui::gtk3::pattern_controller::stop(leds, runner),
     }
+}
 
-    Ok(())
+pub fn pattern(button: &gtk::ToggleButton, combo: &gtk::ComboBoxText, leds: &Arc<Mutex<ShiftRegister>>, runner: &Arc<Mutex<Option<PatternRunner>>>) -> Result<()> {
+    let pattern = match combo.get_active_id().as_ref().map(|id| id.as_str()) {
+        Some("knight_rider") => Pattern::KnightRider,
+        Some("blink") => Pattern::Blink,
+        Some("fill") => Pattern::Fill,
+        _ => Pattern::Chase,
+    };
+    match button.get_active() {
+        true => ::gui::gtk3::pattern_controller::start(leds, runner, pattern, true),
+        false => ::gui::gtk3::pattern_controller::stop(leds, runner),
+    }
 }
 
 pub fn set(button: &gtk::ToggleButton, leds: &Arc<Mutex<ShiftRegister>>, num: u64) -> Result<()> {
@@ -51,3 +61,20 @@ pub fn set(button: &gtk::ToggleButton, leds: &Arc<Mutex<ShiftRegister>>, num: u6
 
     Ok(())
 }
+
+pub fn blank(button: &gtk::ToggleButton, leds: &Arc<Mutex<ShiftRegister>>) -> Result<()> {
+    let mut leds = leds.lock().unwrap();
+    match button.get_active() {
+        true => leds.disable_outputs()?,
+        false => leds.enable_outputs()?,
+    }
+
+    Ok(())
+}
+
+pub fn brightness(scale: &gtk::Scale, leds: &Arc<Mutex<ShiftRegister>>) -> Result<()> {
+    let mut leds = leds.lock().unwrap();
+    leds.set_brightness(scale.get_value().round() as u8)?;
+
+    Ok(())
+}
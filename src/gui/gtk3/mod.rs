@@ -1,9 +1,21 @@
+mod diagnostic_controller;
+mod error_reporter;
+#[cfg(feature = "development")]
+mod front_panel;
 mod leds_controller;
+mod output_buttons;
+mod output_grid;
+mod pattern_controller;
 mod relais_controller;
+mod sequence_controller;
 mod static_resource;    // Zur Einbindung der .gresource Datei
 mod tests_index;
 
+use profile::HardwareProfile;
+use std::path::PathBuf;
 
-pub fn launch() {
-    tests_index::launch();
+
+/// Startet die GUI, die Prüfsequenzen werden aus `sequence_dir` angeboten
+pub fn launch(profile: HardwareProfile, sequence_dir: PathBuf) {
+    tests_index::launch(profile, sequence_dir);
 }
@@ -0,0 +1,88 @@
+//! Toggle Buttons der einzelnen Ausgänge eines Shift Registers
+//!
+//! Die Buttons zeigen immer den tatsächlichen Stand der Ausgänge, auch wenn dieser über einen
+//! Test, ein Muster oder einen Reset geändert wurde. Dazu wird `ShiftRegister::subscribe()`
+//! abgefragt und jeder Button auf sein Bit gesetzt.
+//!
+//! `set_active()` löst bei einem `gtk::ToggleButton` auch das `clicked` Signal aus. Solange die
+//! Buttons hier gesetzt werden, liefert `updating()` deshalb `true` und die `clicked` Handler der
+//! Buttons dürfen nichts tun.
+extern crate glib;
+
+use gtk;
+use gtk::prelude::*;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+
+/// Abfrage Intervall der Zustandsänderungen in Millisekunden
+const POLL_MS: u32 = 50;
+
+#[derive(Clone)]
+pub struct OutputButtons {
+    /// `buttons[0]` gehört zu Ausgang 1
+    buttons: Vec<gtk::ToggleButton>,
+    updating: Rc<Cell<bool>>,
+}
+
+impl OutputButtons {
+    pub fn new(buttons: Vec<gtk::ToggleButton>) -> Self {
+        OutputButtons {
+            buttons: buttons,
+            updating: Rc::new(Cell::new(false)),
+        }
+    }
+
+    /// `true` solange die Buttons vom Programm aus gesetzt werden, siehe Modul Dokumentation
+    pub fn updating(&self) -> bool {
+        self.updating.get()
+    }
+
+    /// Setzt `button` ohne dass sein `clicked` Handler etwas tut
+    pub fn set_active(&self, button: &gtk::ToggleButton, active: bool) {
+        if button.get_active() != active {
+            self.updating.set(true);
+            button.set_active(active);
+            self.updating.set(false);
+        }
+    }
+
+    /// Setzt jeden Button auf sein Bit im `data` Buffer
+    pub fn update(&self, data: &[u8]) {
+        for (index, button) in self.buttons.iter().enumerate() {
+            let active = data.get(index / 8).map(|byte| byte & (1 << (index % 8)) != 0).unwrap_or(false);
+            self.set_active(button, active);
+        }
+    }
+
+    /// Fragt die Zustandsänderungen aus `ShiftRegister::subscribe()` ab und aktualisiert die Buttons
+    ///
+    /// # Arguments
+    /// * `changes`     - Empfänger der Zustandsänderungen
+    /// * `changed`     - Wird nach jeder Aktualisierung aufgerufen, z.B. für die Schaltspiele
+    pub fn watch<F>(&self, changes: Receiver<Vec<u8>>, changed: F)
+        where F: Fn() + 'static
+    {
+        let buttons = self.clone();
+        gtk::timeout_add(POLL_MS, move || {
+            // Nur der neueste Stand ist interessant
+            let (mut latest, mut connected) = (None, true);
+            loop {
+                match changes.try_recv() {
+                    Ok(data) => latest = Some(data),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        connected = false;
+                        break;
+                    },
+                }
+            }
+            if let Some(data) = latest {
+                buttons.update(&data);
+                changed();
+            }
+            glib::Continue(connected)
+        });
+    }
+}
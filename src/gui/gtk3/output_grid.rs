@@ -0,0 +1,51 @@
+//! Buttons der einzelnen Ausgänge, erzeugt aus dem Hardware Profil
+//!
+//! Anzahl, Beschriftung, Farbe und Anordnung der Buttons kommen aus dem `ShiftRegister`, siehe
+//! `outputs`, `labels`, `colours` und `positions`. So passt die GUI auch zu einer Front mit mehr
+//! oder weniger Ausgängen, ohne dass die `main.ui` angepasst werden muss.
+//!
+//! Ein Button mit Farbe bekommt die CSS Klasse aus `css_class()`, eingeschaltet leuchtet er dann
+//! in dieser Farbe. Die CSS Regeln liefert `css()`.
+use gtk;
+use gtk::prelude::*;
+use shift_register::*;
+
+
+/// CSS Klasse der Buttons in der Farbe `colour`
+pub fn css_class(colour: Colour) -> String {
+    format!("output-{}", colour.css())
+}
+
+/// CSS Regeln für alle Farben, eingeschaltete Buttons werden in ihrer Farbe gezeigt
+pub fn css() -> String {
+    Colour::all()
+        .iter()
+        .map(|colour| {
+            let (r, g, b) = colour.rgb();
+            format!(".{}:checked {{ background-image: none; background-color: rgb({}, {}, {}); }}\n",
+                    css_class(*colour),
+                    (r * 255.0) as u8,
+                    (g * 255.0) as u8,
+                    (b * 255.0) as u8)
+        })
+        .collect()
+}
+
+/// Erzeugt für jeden Ausgang einen Button im `grid`
+///
+/// Liefert die Buttons, `buttons[0]` gehört zu Ausgang 1.
+pub fn build(grid: &gtk::Grid, register: &ShiftRegister) -> Vec<gtk::ToggleButton> {
+    (1..register.outputs as u64 + 1)
+        .map(|num| {
+            let button = gtk::ToggleButton::new_with_label(&register.label(num));
+            if let Some(colour) = register.colour(num) {
+                if let Some(style_context) = button.get_style_context() {
+                    style_context.add_class(&css_class(colour));
+                }
+            }
+            let (column, row) = register.grid_position(num);
+            grid.attach(&button, column, row, 1, 1);
+            button
+        })
+        .collect()
+}
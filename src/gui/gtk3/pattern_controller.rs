@@ -0,0 +1,31 @@
+use errors::*;
+use shift_register::*;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+
+/// Anzeigedauer eines Frames der Muster in der GUI
+const PATTERN_INTERVAL_MS: u64 = 100;
+
+/// Startet das Muster im Hintergrund, ein bereits laufendes Muster wird vorher abgebrochen
+pub fn start(register: &Arc<Mutex<ShiftRegister>>, runner: &Arc<Mutex<Option<PatternRunner>>>, pattern: Pattern, repeat: bool) -> Result<()> {
+    let mut runner = runner.lock().unwrap();
+    if let Some(old) = runner.take() {
+        old.stop()?;
+    }
+    *runner = Some(PatternRunner::start(register.clone(), pattern, Duration::from_millis(PATTERN_INTERVAL_MS), repeat));
+
+    Ok(())
+}
+
+/// Bricht das laufende Muster ab und schaltet alle Ausgänge aus
+pub fn stop(register: &Arc<Mutex<ShiftRegister>>, runner: &Arc<Mutex<Option<PatternRunner>>>) -> Result<()> {
+    let old = runner.lock().unwrap().take();
+    let result = match old {
+        Some(old) => old.stop(),
+        None => Ok(()),
+    };
+    register.lock().unwrap().reset()?;
+
+    result
+}
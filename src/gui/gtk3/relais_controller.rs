@@ -8,7 +8,14 @@ use std::sync::{Arc, Mutex};
 pub fn all(button: &gtk::ToggleButton, relais: &Arc<Mutex<ShiftRegister>>) -> Result<()> {
     let mut relais = relais.lock().unwrap();
     match button.get_active() {
-        true => relais.all()?,
+        true => {
+            // Verriegelte Relais bleiben aus, welche steht im Tooltip
+            let skipped: Vec<String> = relais.all()?.iter().map(|num| relais.label(*num)).collect();
+            match skipped.is_empty() {
+                true => button.set_tooltip_text(None),
+                false => button.set_tooltip_text(Some(&format!("Wegen Verriegelung aus: {}", skipped.join(", ")))),
+            }
+        },
         false => relais.reset()?,
     }
 
@@ -16,23 +23,12 @@ pub fn all(button: &gtk::ToggleButton, relais: &Arc<Mutex<ShiftRegister>>) -> Re
 }
 
 pub fn random(button: &gtk::ToggleButton, relais: &Arc<Mutex<ShiftRegister>>) -> Result<()> {
-    let mut relais = relais.lock().unwrap();
-    match button.get_active() {
-        true => relais.test_random()?,
-        false => relais.reset()?,
-    }
-
-    Ok(())}
-
-pub fn one_after_one(button: &gtk::ToggleButton, relais: &Arc<Mutex<ShiftRegister>>) -> Result<()> {
     let mut relais = relais.lock().unwrap();
     match button.get_active() {
         true => {
-            for i in 1..10 {
-                relais.set(i);
-
-                ::std::thread::sleep(::std::time::Duration::from_millis(100));
-            }
+            // Seed anzeigen, damit ein Fehler mit dem selben Seed nachgestellt werden kann
+            let test = relais.test_random(None)?;
+            button.set_tooltip_text(Some(&format!("Seed {}", test.seed)));
         },
         false => relais.reset()?,
     }
@@ -40,6 +36,13 @@ pub fn one_after_one(button: &gtk::ToggleButton, relais: &Arc<Mutex<ShiftRegiste
     Ok(())
 }
 
+pub fn one_after_one(button: &gtk::ToggleButton, relais: &Arc<Mutex<ShiftRegister>>, runner: &Arc<Mutex<Option<PatternRunner>>>) -> Result<()> {
+    match button.get_active() {
+        true => ::gui::gtk3::pattern_controller::start(relais, runner, Pattern::Fill, false),
+        false => ::gui::gtk3::pattern_controller::stop(relais, runner),
+    }
+}
+
 pub fn set(button: &gtk::ToggleButton, relais: &Arc<Mutex<ShiftRegister>>, num: u64) -> Result<()> {
     let mut relais = relais.lock().unwrap();
     match button.get_active() {
@@ -49,3 +52,33 @@ pub fn set(button: &gtk::ToggleButton, relais: &Arc<Mutex<ShiftRegister>>, num:
 
     Ok(())
 }
+
+/// Zeigt die Schaltspiele der Relais auf den Buttons und verschlissene Relais im Label an
+pub fn show_cycles(buttons: &[gtk::ToggleButton], label: &gtk::Label, relais: &Arc<Mutex<ShiftRegister>>) {
+    let relais = relais.lock().unwrap();
+    let worn = relais.cycle_counter.worn();
+    for (index, button) in buttons.iter().enumerate() {
+        let num = index as u64 + 1;
+        let cycles = relais.cycle_counter.cycles(num);
+        match worn.contains(&num) {
+            true => button.set_label(&format!("⚠ {}\n{} Schaltspiele", relais.label(num), cycles)),
+            false => button.set_label(&format!("{}\n{} Schaltspiele", relais.label(num), cycles)),
+        }
+    }
+
+    match (relais.cycle_counter.warning_threshold, worn.is_empty()) {
+        (None, _) => label.set_text("Schaltspiele, keine Warnschwelle konfiguriert"),
+        (Some(threshold), true) => label.set_text(&format!("Schaltspiele, kein Relais über der Warnschwelle von {}", threshold)),
+        (Some(threshold), false) => {
+            let worn: Vec<String> = worn.iter().map(|num| relais.label(*num)).collect();
+            label.set_text(&format!("Warnschwelle von {} Schaltspielen erreicht: {}", threshold, worn.join(", ")))
+        },
+    }
+}
+
+pub fn reset_cycles(relais: &Arc<Mutex<ShiftRegister>>) -> Result<()> {
+    let mut relais = relais.lock().unwrap();
+    relais.cycle_counter.reset()?;
+
+    Ok(())
+}
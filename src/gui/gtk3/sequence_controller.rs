@@ -0,0 +1,96 @@
+use errors::*;
+use gtk;
+use gtk::prelude::*;
+use sequence::*;
+use shift_register::*;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+
+/// Laufende Prüfsequenz
+pub type SequenceRun = Arc<Mutex<Option<SequenceRunner>>>;
+
+/// Füllt die Combo Box mit den Prüfsequenzen aus dem Verzeichnis, angezeigt wird der Dateiname
+pub fn fill(combo: &gtk::ComboBoxText, dir: &Path) -> Result<()> {
+    combo.remove_all();
+    for path in Sequence::list(dir)? {
+        if let Some(name) = path.file_stem().and_then(|name| name.to_str()) {
+            combo.append_text(name);
+        }
+    }
+    combo.set_active(0);
+
+    Ok(())
+}
+
+/// Startet die ausgewählte Prüfsequenz bzw. bricht die laufende ab
+pub fn run(button: &gtk::ToggleButton, combo: &gtk::ComboBoxText, dir: &Path, label: &gtk::Label,
+           relais: &Arc<Mutex<ShiftRegister>>, leds: &Arc<Mutex<ShiftRegister>>,
+           modbus: &Option<ModbusConfig>, run: &SequenceRun) -> Result<()> {
+    let mut run = run.lock().unwrap();
+    if let Some(old) = run.take() {
+        old.stop()?;
+        label.set_text("Prüfsequenz abgebrochen");
+    }
+    if !button.get_active() {
+        return Ok(());
+    }
+
+    let name = match combo.get_active_text() {
+        Some(name) => name,
+        None => bail!("Keine Prüfsequenz ausgewählt"),
+    };
+    let path = dir.join(format!("{}.json", name));
+    let sequence = Sequence::load(&path)
+        .chain_err(|| format!("Prüfsequenz {} konnte nicht geladen werden", path.display()))?;
+    let modbus = modbus.clone().map(|config| Box::new(RtuBus::new(config)) as Box<ModbusBus + Send>);
+    label.set_text(&format!("{} gestartet", sequence.name));
+    *run = Some(SequenceRunner::start(sequence, relais.clone(), leds.clone(), modbus));
+
+    Ok(())
+}
+
+/// Zeigt die Nachrichten der laufenden Prüfsequenz an
+///
+/// Bei einer Frage werden die Ja/Nein Buttons freigegeben. Ist die Sequenz beendet, wird das
+/// Ergebnis angezeigt und `Some` mit einem eventuellen Fehler der Sequenz zurückgegeben.
+pub fn poll(label: &gtk::Label, button_yes: &gtk::Button, button_no: &gtk::Button, run: &SequenceRun) -> Option<Result<()>> {
+    let mut run = run.lock().unwrap();
+    loop {
+        let event = match *run {
+            Some(ref runner) => runner.try_event(),
+            // Abgebrochen
+            None => {
+                button_yes.set_sensitive(false);
+                button_no.set_sensitive(false);
+                return Some(Ok(()));
+            },
+        };
+        match event {
+            Some(Event::Step(index, total, description)) => {
+                label.set_text(&format!("Schritt {} von {}: {}", index + 1, total, description));
+            },
+            Some(Event::Question(question)) => {
+                label.set_text(&question);
+                button_yes.set_sensitive(true);
+                button_no.set_sensitive(true);
+            },
+            Some(Event::Finished(result)) => {
+                *run = None;
+                button_yes.set_sensitive(false);
+                button_no.set_sensitive(false);
+                return Some(result.map(|report| label.set_text(&report.to_string())));
+            },
+            None => return None,
+        }
+    }
+}
+
+/// Beantwortet die offene Frage der Prüfsequenz
+pub fn answer(yes: bool, button_yes: &gtk::Button, button_no: &gtk::Button, run: &SequenceRun) {
+    if let Some(ref runner) = *run.lock().unwrap() {
+        runner.answer(yes);
+    }
+    button_yes.set_sensitive(false);
+    button_no.set_sensitive(false);
+}
@@ -10,9 +10,14 @@ use gtk;
 use gtk::prelude::*;
 use self::glib_sys::gpointer;
 use self::glib::translate::ToGlibPtr;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
+use ::gui::gtk3::error_reporter::ErrorReporter;
+use ::gui::gtk3::output_buttons::OutputButtons;
+use ::gui::gtk3::sequence_controller::{self, SequenceRun};
+use ::profile::HardwareProfile;
 use ::shift_register::*;
 
 
@@ -36,8 +41,9 @@ fn window_main_setup(window: &gtk::Window) -> Result<()> {
     let css_style_provider = gtk::CssProvider::new();
     // Das CSS File sollte aus dem Dateisystem mit `include_str!` eingebunden werden.
     // let css_file = include_str!("css_file.css");
-    let css_file = "GtkToggleButton .toggle { background-color: red; }"; // In diesem Testfile wird Anstelle des CSS Files ein String verwendet
-    css_style_provider.load_from_data(css_file).unwrap();
+    // Eingeschaltete Buttons der Ausgänge leuchten in der Farbe aus dem Hardware Profil
+    let css_file = ::gui::gtk3::output_grid::css();
+    css_style_provider.load_from_data(&css_file).unwrap();
     gtk::StyleContext::add_provider_for_screen(&screen, &css_style_provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
 
     #[cfg(not(feature = "development"))]
@@ -47,7 +53,48 @@ fn window_main_setup(window: &gtk::Window) -> Result<()> {
     Ok(())
 }
 
-pub fn launch() {
+// Überwacht das Muster, endet es mit einem Fehler wird dieser angezeigt und der Button zurückgesetzt
+fn watch_pattern(button: &gtk::ToggleButton, runner: &Arc<Mutex<Option<PatternRunner>>>, errors: &ErrorReporter) {
+    gtk::timeout_add(100, clone!(button, runner, errors => move || {
+        let finished = match *runner.lock().unwrap() {
+            Some(ref runner) => runner.is_finished(),
+            None => return glib::Continue(false),
+        };
+        if !finished {
+            return glib::Continue(true);
+        }
+
+        let runner = runner.lock().unwrap().take();
+        if let Some(Err(err)) = runner.map(|runner| runner.stop()) {
+            // Das Muster ist schon beendet, der Stand der Ausgänge bleibt für die Fehlersuche erhalten
+            errors.report(&err);
+            errors.set_active(&button, false);
+        }
+        glib::Continue(false)
+    }));
+}
+
+// Zeigt den Fortschritt der Prüfsequenz, an ihrem Ende wird der Button zurückgesetzt
+fn watch_sequence(button: &gtk::ToggleButton, label: &gtk::Label, button_yes: &gtk::Button, button_no: &gtk::Button,
+                  run: &SequenceRun, errors: &ErrorReporter) {
+    gtk::timeout_add(100, clone!(button, label, button_yes, button_no, run, errors => move || {
+        match sequence_controller::poll(&label, &button_yes, &button_no, &run) {
+            None => glib::Continue(true),
+            Some(result) => {
+                if let Err(err) = result {
+                    label.set_text("Prüfsequenz mit Fehler beendet");
+                    errors.report(&err);
+                }
+                if button.get_active() {
+                    errors.set_active(&button, false);
+                }
+                glib::Continue(false)
+            },
+        }
+    }));
+}
+
+pub fn launch(profile: HardwareProfile, sequence_dir: PathBuf) {
     gtk::init().unwrap_or_else(|_| {
         panic!(format!("{}: GTK konnte nicht initalisiert werden.",
         env!("CARGO_PKG_NAME")))
@@ -67,8 +114,16 @@ pub fn launch() {
 
     let window_main: gtk::Window = builder.get_object("window_main").unwrap();
 
-    let mut relais = Arc::new(Mutex::new(ShiftRegister::new(ShiftRegisterType::RELAIS)));
-    let mut leds = Arc::new(Mutex::new(ShiftRegister::new(ShiftRegisterType::LED)));
+    let mut relais = Arc::new(Mutex::new(profile.relais));
+    let mut leds = Arc::new(Mutex::new(profile.leds));
+    let modbus = profile.modbus;
+    // Laufende Muster, die Muster laufen im Hintergrund damit die GUI bedienbar bleibt
+    let relais_runner: Arc<Mutex<Option<PatternRunner>>> = Arc::new(Mutex::new(None));
+    let leds_runner: Arc<Mutex<Option<PatternRunner>>> = Arc::new(Mutex::new(None));
+    let diagnostic_run: ::gui::gtk3::diagnostic_controller::DiagnosticRun = Arc::new(Mutex::new(None));
+    let sequence_run: SequenceRun = Arc::new(Mutex::new(None));
+    safe_state::register(&relais);
+    safe_state::register(&leds);
 
 
     let button_test_relais_all: gtk::ToggleButton = builder.get_object("button_test_relais_all").unwrap();
@@ -77,163 +132,214 @@ pub fn launch() {
     let button_test_leds_random: gtk::ToggleButton = builder.get_object("button_test_leds_random").unwrap();
     let button_test_relais_one_after_one: gtk::ToggleButton = builder.get_object("button_test_relais_one_after_one").unwrap();
     let button_test_leds_one_after_one: gtk::ToggleButton = builder.get_object("button_test_leds_one_after_one").unwrap();
-
-    let button_relais1: gtk::ToggleButton = builder.get_object("button_relais1").unwrap();
-    let button_relais2: gtk::ToggleButton = builder.get_object("button_relais2").unwrap();
-    let button_relais3: gtk::ToggleButton = builder.get_object("button_relais3").unwrap();
-    let button_relais4: gtk::ToggleButton = builder.get_object("button_relais4").unwrap();
-    let button_relais5: gtk::ToggleButton = builder.get_object("button_relais5").unwrap();
-    let button_relais6: gtk::ToggleButton = builder.get_object("button_relais6").unwrap();
-    let button_relais7: gtk::ToggleButton = builder.get_object("button_relais7").unwrap();
-    let button_relais8: gtk::ToggleButton = builder.get_object("button_relais8").unwrap();
-    let button_relais9: gtk::ToggleButton = builder.get_object("button_relais9").unwrap();
-
-    let button_led1: gtk::ToggleButton = builder.get_object("button_led1").unwrap();
-    let button_led2: gtk::ToggleButton = builder.get_object("button_led2").unwrap();
-    let button_led3: gtk::ToggleButton = builder.get_object("button_led3").unwrap();
-    let button_led4: gtk::ToggleButton = builder.get_object("button_led4").unwrap();
-    let button_led5: gtk::ToggleButton = builder.get_object("button_led5").unwrap();
-    let button_led6: gtk::ToggleButton = builder.get_object("button_led6").unwrap();
-    let button_led7: gtk::ToggleButton = builder.get_object("button_led7").unwrap();
-    let button_led8: gtk::ToggleButton = builder.get_object("button_led8").unwrap();
-    let button_led9: gtk::ToggleButton = builder.get_object("button_led9").unwrap();
-    let button_led10: gtk::ToggleButton = builder.get_object("button_led10").unwrap();
-    let button_led11: gtk::ToggleButton = builder.get_object("button_led11").unwrap();
-    let button_led12: gtk::ToggleButton = builder.get_object("button_led12").unwrap();
-    let button_led13: gtk::ToggleButton = builder.get_object("button_led13").unwrap();
-    let button_led14: gtk::ToggleButton = builder.get_object("button_led14").unwrap();
-    let button_led15: gtk::ToggleButton = builder.get_object("button_led15").unwrap();
-    let button_led16: gtk::ToggleButton = builder.get_object("button_led16").unwrap();
-    let button_led17: gtk::ToggleButton = builder.get_object("button_led17").unwrap();
-    let button_led18: gtk::ToggleButton = builder.get_object("button_led18").unwrap();
-    let button_led19: gtk::ToggleButton = builder.get_object("button_led19").unwrap();
-    let button_led20: gtk::ToggleButton = builder.get_object("button_led20").unwrap();
+    let combo_leds_pattern: gtk::ComboBoxText = builder.get_object("combo_leds_pattern").unwrap();
+    let button_test_leds_pattern: gtk::ToggleButton = builder.get_object("button_test_leds_pattern").unwrap();
+    let combo_diagnostic_register: gtk::ComboBoxText = builder.get_object("combo_diagnostic_register").unwrap();
+    let combo_diagnostic_pattern: gtk::ComboBoxText = builder.get_object("combo_diagnostic_pattern").unwrap();
+    let button_diagnostic_step: gtk::Button = builder.get_object("button_diagnostic_step").unwrap();
+    let button_diagnostic_flag: gtk::Button = builder.get_object("button_diagnostic_flag").unwrap();
+    let label_diagnostic: gtk::Label = builder.get_object("label_diagnostic").unwrap();
+    let combo_sequence: gtk::ComboBoxText = builder.get_object("combo_sequence").unwrap();
+    let button_sequence_run: gtk::ToggleButton = builder.get_object("button_sequence_run").unwrap();
+    let button_sequence_yes: gtk::Button = builder.get_object("button_sequence_yes").unwrap();
+    let button_sequence_no: gtk::Button = builder.get_object("button_sequence_no").unwrap();
+    let label_sequence: gtk::Label = builder.get_object("label_sequence").unwrap();
+
+    // Die Buttons der Ausgänge werden aus dem Hardware Profil erzeugt
+    let grid_relais: gtk::Grid = builder.get_object("grid_relais").unwrap();
+    let relais_buttons = ::gui::gtk3::output_grid::build(&grid_relais, &relais.lock().unwrap());
+    let relais_outputs = OutputButtons::new(relais_buttons.clone());
+    let label_relais_cycles: gtk::Label = builder.get_object("label_relais_cycles").unwrap();
+    let button_relais_reset_cycles: gtk::Button = builder.get_object("button_relais_reset_cycles").unwrap();
+
+    let grid_leds: gtk::Grid = builder.get_object("grid_leds").unwrap();
+    let leds_buttons = ::gui::gtk3::output_grid::build(&grid_leds, &leds.lock().unwrap());
+    let leds_outputs = OutputButtons::new(leds_buttons.clone());
+
+    let button_leds_blank: gtk::ToggleButton = builder.get_object("button_leds_blank").unwrap();
+    let scale_leds_brightness: gtk::Scale = builder.get_object("scale_leds_brightness").unwrap();
 
     let info_bar: gtk::InfoBar = builder.get_object("info_bar").unwrap();
+    let label_info_bar: gtk::Label = builder.get_object("label_info_bar").unwrap();
+    // Alle Fehler der Controller werden hierüber angezeigt und geloggt
+    let errors = ErrorReporter::new(&info_bar, &label_info_bar);
 
-    // Rufe Funktion für die Basis Fenster Konfiguration auf
-    window_main_setup(&window_main);
+    // Bei den Relais die Schaltspiele mit in die Beschriftung
+    ::gui::gtk3::relais_controller::show_cycles(&relais_buttons, &label_relais_cycles, &relais);
+
+    // Die Buttons der einzelnen Ausgänge zeigen immer den tatsächlichen Stand, egal wodurch er
+    // geändert wurde. Bei den Relais werden dabei auch die Schaltspiele neu angezeigt.
+    relais_outputs.update(&relais.lock().unwrap().data);
+    relais_outputs.watch(relais.lock().unwrap().subscribe(), clone!(relais, relais_buttons, label_relais_cycles => move || {
+        ::gui::gtk3::relais_controller::show_cycles(&relais_buttons, &label_relais_cycles, &relais);
+    }));
+    leds_outputs.update(&leds.lock().unwrap().data);
+    leds_outputs.watch(leds.lock().unwrap().subscribe(), || {});
 
-    { // Hide info_bar
-            let info_bar = info_bar.clone();
-            info_bar.connect_response(move |info_bar, _| info_bar.hide());
+    // Rufe Funktion für die Basis Fenster Konfiguration auf
+    if let Err(err) = window_main_setup(&window_main) {
+        errors.report(&err);
     }
 
-    button_test_relais_all.connect_clicked(clone!(relais => move |button| {
-        ::gui::gtk3::relais_controller::all(button, &relais);
+    button_test_relais_all.connect_clicked(clone!(relais, errors => move |button| {
+        if errors.reverting() {
+            return;
+        }
+        if let Err(err) = ::gui::gtk3::relais_controller::all(button, &relais) {
+            errors.revert(button, &err);
+        }
     }));
 
-    button_test_leds_all.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::all(button, &leds);
+    button_test_leds_all.connect_clicked(clone!(leds, errors => move |button| {
+        if errors.reverting() {
+            return;
+        }
+        if let Err(err) = ::gui::gtk3::leds_controller::all(button, &leds) {
+            errors.revert(button, &err);
+        }
     }));
 
-    button_test_relais_random.connect_clicked(clone!(relais => move |button| {
-        ::gui::gtk3::relais_controller::random(button, &relais);
+    button_diagnostic_step.connect_clicked(clone!(combo_diagnostic_register, combo_diagnostic_pattern, label_diagnostic, relais, leds, diagnostic_run, errors => move |_| {
+        if let Err(err) = ::gui::gtk3::diagnostic_controller::step(&combo_diagnostic_register, &combo_diagnostic_pattern,
+                                                                   &label_diagnostic, &relais, &leds, &diagnostic_run) {
+            errors.report(&err);
+        }
     }));
-
-    button_test_leds_random.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::random(button, &leds);
+    button_diagnostic_flag.connect_clicked(clone!(label_diagnostic, diagnostic_run => move |_| {
+        ::gui::gtk3::diagnostic_controller::flag(&label_diagnostic, &diagnostic_run);
     }));
+    for combo in &[combo_diagnostic_register.clone(), combo_diagnostic_pattern.clone()] {
+        combo.connect_changed(clone!(label_diagnostic, diagnostic_run => move |_| {
+            ::gui::gtk3::diagnostic_controller::cancel(&label_diagnostic, &diagnostic_run);
+        }));
+    }
 
-    button_test_relais_one_after_one.connect_clicked(clone!(relais => move |button| {
-        ::gui::gtk3::relais_controller::one_after_one(button, &relais);
+    button_test_relais_random.connect_clicked(clone!(relais, errors => move |button| {
+        if errors.reverting() {
+            return;
+        }
+        if let Err(err) = ::gui::gtk3::relais_controller::random(button, &relais) {
+            errors.revert(button, &err);
+        }
     }));
 
-    button_test_leds_one_after_one.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::one_after_one(button, &leds);
+    button_test_leds_random.connect_clicked(clone!(leds, errors => move |button| {
+        if errors.reverting() {
+            return;
+        }
+        if let Err(err) = ::gui::gtk3::leds_controller::random(button, &leds) {
+            errors.revert(button, &err);
+        }
     }));
 
-
-    button_relais1.connect_clicked(clone!(relais => move |button| {
-        ::gui::gtk3::relais_controller::set(button, &relais, 1);
-    }));
-    button_relais2.connect_clicked(clone!(relais => move |button| {
-        ::gui::gtk3::relais_controller::set(button, &relais, 2);
-    }));
-    button_relais3.connect_clicked(clone!(relais => move |button| {
-        ::gui::gtk3::relais_controller::set(button, &relais, 3);
-    }));
-    button_relais4.connect_clicked(clone!(relais => move |button| {
-        ::gui::gtk3::relais_controller::set(button, &relais, 4);
-    }));
-    button_relais5.connect_clicked(clone!(relais => move |button| {
-        ::gui::gtk3::relais_controller::set(button, &relais, 5);
-    }));
-    button_relais6.connect_clicked(clone!(relais => move |button| {
-        ::gui::gtk3::relais_controller::set(button, &relais, 6);
-    }));
-    button_relais7.connect_clicked(clone!(relais => move |button| {
-        ::gui::gtk3::relais_controller::set(button, &relais, 7);
+    button_test_relais_one_after_one.connect_clicked(clone!(relais, relais_runner, errors => move |button| {
+        if errors.reverting() {
+            return;
+        }
+        match ::gui::gtk3::relais_controller::one_after_one(button, &relais, &relais_runner) {
+            Ok(_) => watch_pattern(button, &relais_runner, &errors),
+            Err(err) => errors.revert(button, &err),
+        }
     }));
-    button_relais8.connect_clicked(clone!(relais => move |button| {
-        ::gui::gtk3::relais_controller::set(button, &relais, 8);
+
+    button_test_leds_one_after_one.connect_clicked(clone!(leds, leds_runner, errors => move |button| {
+        if errors.reverting() {
+            return;
+        }
+        match ::gui::gtk3::leds_controller::one_after_one(button, &leds, &leds_runner) {
+            Ok(_) => watch_pattern(button, &leds_runner, &errors),
+            Err(err) => errors.revert(button, &err),
+        }
     }));
-    button_relais9.connect_clicked(clone!(relais => move |button| {
-        ::gui::gtk3::relais_controller::set(button, &relais, 9);
+
+    button_test_leds_pattern.connect_clicked(clone!(leds, leds_runner, combo_leds_pattern, errors => move |button| {
+        if errors.reverting() {
+            return;
+        }
+        match ::gui::gtk3::leds_controller::pattern(button, &combo_leds_pattern, &leds, &leds_runner) {
+            Ok(_) => watch_pattern(button, &leds_runner, &errors),
+            Err(err) => errors.revert(button, &err),
+        }
     }));
 
 
-    button_led1.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 1);
-    }));
-    button_led2.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 2);
-    }));
-    button_led3.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 3);
-    }));
-    button_led4.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 4);
-    }));
-    button_led5.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 5);
-    }));
-    button_led6.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 6);
-    }));
-    button_led7.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 7);
-    }));
-    button_led8.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 8);
-    }));
-    button_led9.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 9);
-    }));
-    button_led10.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 10);
-    }));
-    button_led11.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 11);
-    }));
-    button_led12.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 12);
-    }));
-    button_led13.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 13);
-    }));
-    button_led14.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 14);
-    }));
-    button_led15.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 15);
+    for (index, button) in relais_buttons.iter().enumerate() {
+        let num = index as u64 + 1;
+        button.connect_clicked(clone!(relais, relais_outputs, errors => move |button| {
+            if relais_outputs.updating() {
+                return;
+            }
+            if let Err(err) = ::gui::gtk3::relais_controller::set(button, &relais, num) {
+                errors.report(&err);
+                relais_outputs.update(&relais.lock().unwrap().data);
+            }
+        }));
+    }
+
+    button_relais_reset_cycles.connect_clicked(clone!(relais, relais_buttons, label_relais_cycles, errors => move |_| {
+        if let Err(err) = ::gui::gtk3::relais_controller::reset_cycles(&relais) {
+            errors.report(&err);
+        }
+        ::gui::gtk3::relais_controller::show_cycles(&relais_buttons, &label_relais_cycles, &relais);
     }));
-    button_led16.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 16);
+
+
+    for (index, button) in leds_buttons.iter().enumerate() {
+        let num = index as u64 + 1;
+        button.connect_clicked(clone!(leds, leds_outputs, errors => move |button| {
+            if leds_outputs.updating() {
+                return;
+            }
+            if let Err(err) = ::gui::gtk3::leds_controller::set(button, &leds, num) {
+                errors.report(&err);
+                leds_outputs.update(&leds.lock().unwrap().data);
+            }
+        }));
+    }
+
+    button_leds_blank.connect_clicked(clone!(leds, errors => move |button| {
+        if errors.reverting() {
+            return;
+        }
+        if let Err(err) = ::gui::gtk3::leds_controller::blank(button, &leds) {
+            errors.revert(button, &err);
+        }
     }));
-    button_led17.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 17);
+    scale_leds_brightness.connect_value_changed(clone!(leds, errors => move |scale| {
+        if let Err(err) = ::gui::gtk3::leds_controller::brightness(scale, &leds) {
+            errors.report(&err);
+        }
     }));
-    button_led18.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 18);
+
+    // Prüfsequenzen aus dem Verzeichnis anbieten
+    if let Err(err) = sequence_controller::fill(&combo_sequence, &sequence_dir) {
+        errors.report(&err);
+    }
+    button_sequence_run.connect_clicked(clone!(combo_sequence, label_sequence, button_sequence_yes, button_sequence_no, relais, leds, modbus, sequence_dir, sequence_run, errors => move |button| {
+        if errors.reverting() {
+            return;
+        }
+        match sequence_controller::run(button, &combo_sequence, &sequence_dir, &label_sequence, &relais, &leds, &modbus, &sequence_run) {
+            Ok(_) => if button.get_active() {
+                watch_sequence(button, &label_sequence, &button_sequence_yes, &button_sequence_no, &sequence_run, &errors);
+            },
+            Err(err) => errors.revert(button, &err),
+        }
     }));
-    button_led19.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 19);
+    button_sequence_yes.connect_clicked(clone!(button_sequence_no, sequence_run => move |button| {
+        sequence_controller::answer(true, button, &button_sequence_no, &sequence_run);
     }));
-    button_led20.connect_clicked(clone!(leds => move |button| {
-        ::gui::gtk3::leds_controller::set(button, &leds, 20);
+    button_sequence_no.connect_clicked(clone!(button_sequence_yes, sequence_run => move |button| {
+        sequence_controller::answer(false, &button_sequence_yes, button, &sequence_run);
     }));
 
+    // Ohne angeschlossene Hardware werden LED und Relais auf der virtuellen Front gezeigt
+    #[cfg(feature = "development")]
+    {
+        let notebook_main: gtk::Notebook = builder.get_object("notebook_main").unwrap();
+        ::gui::gtk3::front_panel::setup(&notebook_main, &leds, &relais);
+    }
+
 
     window_main.show_all();
     info_bar.hide();
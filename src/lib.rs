@@ -2,18 +2,28 @@
 #![recursion_limit = "1024"]
 
 #[macro_use] extern crate error_chain;
+#[macro_use] extern crate lazy_static;
+#[macro_use] extern crate log;
 #[macro_use] extern crate serde_derive;
-extern crate gdk;
-extern crate gtk;
+#[cfg(feature = "gui")] extern crate gdk;
+extern crate gpio_cdev;
+#[cfg(feature = "gui")] extern crate gtk;
+extern crate libc;
 extern crate libmodbus_rs;
 extern crate rand;
 extern crate serde_json;
 extern crate serde;
+extern crate spidev;
 extern crate sysfs_gpio;
 
-#[macro_use] mod macros;
+#[cfg(feature = "gui")] #[macro_use] mod macros;
+#[cfg(feature = "gui")]
 pub mod gui {
     pub mod gtk3;
 }
+pub mod cli;
 pub mod errors;
+pub mod logger;
+pub mod profile;
+pub mod sequence;
 pub mod shift_register;
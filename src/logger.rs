@@ -0,0 +1,65 @@
+//! Log auf stderr und in eine Log Datei
+//!
+//! Fehler die in der GUI angezeigt werden, landen zusätzlich im Log. So lässt sich auch nach einem
+//! Neustart des Prüfplatzes noch nachvollziehen was schief gegangen ist. An die Log Datei wird
+//! immer nur angehängt.
+use errors::*;
+use log::{self, LogLevelFilter, LogMetadata, LogRecord};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+
+/// Pfad der Log Datei, wenn keiner angegeben wurde
+pub const DEFAULT_LOG_PATH: &'static str = "/var/log/xmz-mod-touch-test-tool.log";
+
+struct Logger {
+    level: LogLevelFilter,
+    file: Option<Mutex<File>>,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &LogMetadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &LogRecord) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        // Sekunden seit 1970, ohne eigene Abhängigkeit für die Formatierung von Zeitstempeln
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+        let line = format!("{} {} {}: {}", seconds, record.level(), record.target(), record.args());
+        // Fehler beim Loggen können nirgends mehr gemeldet werden
+        let _ = writeln!(io::stderr(), "{}", line);
+        if let Some(ref file) = self.file {
+            let mut file = file.lock().unwrap_or_else(|err| err.into_inner());
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+}
+
+/// Installiert das Log, das geht nur einmal pro Programmlauf
+///
+/// # Arguments
+/// * `path`    - Log Datei, `None` schreibt nur auf stderr
+/// * `level`   - Höchstes Log Level das noch ausgegeben wird
+pub fn init<P: AsRef<Path>>(path: Option<P>, level: LogLevelFilter) -> Result<()> {
+    let file = match path {
+        Some(path) => Some(Mutex::new(try!(OpenOptions::new().create(true).append(true).open(path)))),
+        None => None,
+    };
+    try!(log::set_logger(|max_level| {
+            max_level.set(level);
+            Box::new(Logger {
+                level: level,
+                file: file,
+            })
+        })
+        .chain_err(|| "Log ist bereits installiert"));
+
+    Ok(())
+}
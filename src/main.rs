@@ -1,22 +1,136 @@
+#[macro_use] extern crate log;
 extern crate xmz_mod_touch_test_tool;
 
+use std::env;
+use std::io::Write;
+use std::path::PathBuf;
+use log::LogLevelFilter;
+use xmz_mod_touch_test_tool::cli::{self, Cli, Command, DEFAULT_STATE_PATH};
 use xmz_mod_touch_test_tool::errors::*;
+use xmz_mod_touch_test_tool::logger::{self, DEFAULT_LOG_PATH};
+use xmz_mod_touch_test_tool::profile::{DEFAULT_PROFILE_PATH, HardwareProfile};
+#[cfg(feature = "gui")]
+use xmz_mod_touch_test_tool::sequence::DEFAULT_SEQUENCE_DIR;
+use xmz_mod_touch_test_tool::shift_register::safe_state;
 
+// Log, Hardware Profil und sicherer Zustand, gleich für GUI und Kommandozeile
+fn init() -> Result<HardwareProfile> {
+    // Log auf stderr und in die Log Datei, ohne `RUST_LOG` werden auch die Infos (z.B. die Seeds
+    // der Zufallstests) ausgegeben
+    let level = env::var("RUST_LOG").ok()
+        .and_then(|level| level.parse().ok())
+        .unwrap_or(LogLevelFilter::Info);
+    let log_path = env::var("XMZ_LOG_FILE").unwrap_or(DEFAULT_LOG_PATH.to_string());
+    if let Err(err) = logger::init(Some(&log_path), level) {
+        // Z.B. auf dem Entwicklungsrechner ohne Schreibrechte in /var/log
+        try!(logger::init(None::<&str>, level));
+        warn!("Log Datei {} konnte nicht geöffnet werden: {}", log_path, err);
+    }
+
+    // Hardware Profil laden, ohne Profil Datei wird das eingebaute Profil verwendet
+    let profile = match env::var("XMZ_HARDWARE_PROFILE") {
+        Ok(path) => try!(HardwareProfile::load(&path)
+            .chain_err(|| format!("Hardware Profil {} konnte nicht geladen werden", path))),
+        Err(_) => try!(HardwareProfile::load_or_default(DEFAULT_PROFILE_PATH)
+            .chain_err(|| format!("Hardware Profil {} konnte nicht geladen werden", DEFAULT_PROFILE_PATH))),
+    };
+
+    // Relais und LED bei Panic, SIGINT und SIGTERM in den sicheren Zustand bringen
+    safe_state::install_handlers();
+
+    Ok(profile)
+}
+
+#[cfg(feature = "gui")]
 fn run() -> Result<()> {
-    xmz_mod_touch_test_tool::gui::gtk3::launch();
+    let profile = try!(init());
+
+    // Verzeichnis der Prüfsequenzen für den Tab "Sequenzen"
+    let sequence_dir = env::var("XMZ_SEQUENCE_DIR").unwrap_or(DEFAULT_SEQUENCE_DIR.to_string());
+
+    // Die GUI schaltet die Ausgänge selbst, der Stand der Kommandozeile stimmt dann nicht mehr
+    safe_state::register_state_file(state_path());
+    safe_state::remove_state_files();
+
+    xmz_mod_touch_test_tool::gui::gtk3::launch(profile, sequence_dir.into());
+
+    // Normales Beenden, die State Datei wird auch gelöscht wenn der sichere Zustand fehlschlägt
+    let result = safe_state::enter_safe_state_all()
+        .chain_err(|| "Shift Register konnten nicht in den sicheren Zustand gebracht werden");
+    safe_state::remove_state_files();
+    try!(result);
 
     Ok(())
 }
 
+// Kommandozeile ohne GUI, die Ausgänge bleiben nach dem Kommando geschaltet
+fn run_cli(cli: &Cli) -> Result<cli::Report> {
+    let profile = try!(init());
 
-fn main() {
+    cli::run(cli, profile, Some(&state_path()))
+}
+
+// State Datei der Kommandozeile
+fn state_path() -> PathBuf {
+    env::var("XMZ_STATE_FILE").unwrap_or(DEFAULT_STATE_PATH.to_string()).into()
+}
+
+// Gibt das Ergebnis bzw. den Fehler aus und liefert den Exit Code
+fn main_cli(args: &[String]) -> i32 {
+    // Auch Fehler beim Parsen sollen als JSON ausgegeben werden
+    let json = args.iter().any(|arg| arg == "--json");
+    let result = Cli::parse(args).and_then(|cli| match cli.command {
+        Command::Help => {
+            print!("{}", cli::USAGE);
+            Ok(None)
+        },
+        _ => run_cli(&cli).map(Some),
+    });
+
+    let stderr = &mut ::std::io::stderr();
+    let errmsg = "Error writing to stderr";
+    match result {
+        Ok(None) => cli::EXIT_OK,
+        Ok(Some(report)) => {
+            match json {
+                true => match report.to_json() {
+                    Ok(output) => println!("{}", output),
+                    Err(err) => {
+                        writeln!(stderr, "error: {}", err).expect(errmsg);
+                        return cli::EXIT_ERROR;
+                    },
+                },
+                false => print!("{}", report),
+            }
+            report.exit_code()
+        },
+        Err(err) => {
+            match (json, cli::error_json(&err)) {
+                (true, Ok(output)) => println!("{}", output),
+                _ => {
+                    writeln!(stderr, "error: {}", err).expect(errmsg);
+                    for e in err.iter().skip(1) {
+                        writeln!(stderr, "caused by: {}", e).expect(errmsg);
+                    }
+                    if cli::exit_code(&err) == cli::EXIT_USAGE {
+                        writeln!(stderr, "\n{}", cli::USAGE).expect(errmsg);
+                    }
+                },
+            }
+            cli::exit_code(&err)
+        },
+    }
+}
+
+
+#[cfg(feature = "gui")]
+fn main_gui() {
     println!("{} Version: {}\n",
         env!("CARGO_PKG_NAME"),
         env!("CARGO_PKG_VERSION"));
 
 
     if let Err(ref e) = run() {
-        use ::std::io::Write;
         let stderr = &mut ::std::io::stderr();
         let errmsg = "Error writing to stderr";
 
@@ -35,3 +149,17 @@ fn main() {
         ::std::process::exit(1);
     }
 }
+
+fn main() {
+    // Mit Argumenten läuft das Programm ohne GUI, siehe `cli`. Ohne `gui` Feature gibt es nur die
+    // Kommandozeile.
+    let args: Vec<String> = env::args().skip(1).collect();
+    #[cfg(feature = "gui")]
+    {
+        if args.is_empty() {
+            return main_gui();
+        }
+    }
+
+    ::std::process::exit(main_cli(&args));
+}
@@ -0,0 +1,148 @@
+//! Hardware Profil der xMZ-Mod-Touch
+//!
+//! Das Hardware Profil beschreibt die Shift Register für LED und Relais, also Pins, Anzahl der
+//! Shift Register und Ausgänge sowie die Bezeichnung der einzelnen Ausgänge. Es wird beim Start
+//! als JSON Datei geladen, so können verschiedene Board Revisionen ohne neu Übersetzen getestet
+//! werden. Ohne Profil Datei wird das eingebaute Profil (`HardwareProfile::default()`) verwendet.
+//!
+//! # Examples
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::profile::HardwareProfile;
+//!
+//! let json = r#"{
+//!     "leds": {
+//!         "register_type": "LED",
+//!         "oe_pin": 276, "ds_pin": 38, "clock_pin": 44, "latch_pin": 40,
+//!         "chips": 3, "outputs": 24
+//!     },
+//!     "relais": {
+//!         "register_type": "RELAIS",
+//!         "oe_pin": 277, "ds_pin": 45, "clock_pin": 39, "latch_pin": 37,
+//!         "chips": 2, "outputs": 12,
+//!         "labels": ["Alarm 1", "Alarm 2", "Störung"]
+//!     }
+//! }"#;
+//!
+//! let profile: HardwareProfile = json.parse().unwrap();
+//! assert_eq!(profile.leds.outputs, 24);
+//! assert_eq!(profile.relais.label(3), "Störung");
+//! assert_eq!(profile.relais.label(4), "Relais 4");
+//! ```
+use errors::*;
+use sequence::ModbusConfig;
+use serde_json;
+use shift_register::{GpioDriver, ShiftRegister, ShiftRegisterType};
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+
+
+/// Pfad des Hardware Profils, wenn keiner angegeben wurde
+pub const DEFAULT_PROFILE_PATH: &'static str = "/etc/xmz-mod-touch-test-tool/profile.json";
+
+/// Schaltspiel Zähler der Relais im eingebauten Profil
+pub const DEFAULT_CYCLE_COUNTER_PATH: &'static str = "/var/lib/xmz-mod-touch-test-tool/relais.json";
+
+/// Beschreibung aller Shift Register eines Boards
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HardwareProfile {
+    pub leds: ShiftRegister,
+    pub relais: ShiftRegister,
+    /// Modbus Schnittstelle des Prüfplatzes für die `ReadModbus` Schritte der Prüfsequenzen
+    #[serde(default)]
+    pub modbus: Option<ModbusConfig>,
+}
+
+/// Eins der beiden Shift Register des Profils, z.B. in Kommandos und Prüfsequenzen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum Register {
+    Relais,
+    Leds,
+}
+
+impl Default for HardwareProfile {
+    /// Eingebautes Profil mit den Pins der aktuellen Board Revision
+    ///
+    /// Die Schaltspiele der Relais werden in `DEFAULT_CYCLE_COUNTER_PATH` gespeichert und gleich
+    /// geladen. Mit dem `development` Feature geben LED und Relais an die virtuelle 74HC595 Kette
+    /// aus.
+    fn default() -> Self {
+        let mut profile = HardwareProfile {
+            leds: ShiftRegister::new(ShiftRegisterType::LED),
+            relais: ShiftRegister::new(ShiftRegisterType::RELAIS),
+            modbus: None,
+        };
+        if cfg!(feature = "development") {
+            profile.leds.gpio = GpioDriver::Simulated;
+            profile.relais.gpio = GpioDriver::Simulated;
+        }
+        profile.relais.cycle_counter.path = Some(DEFAULT_CYCLE_COUNTER_PATH.into());
+        // `Default` kann keinen Fehler liefern, ohne geladene Zähler wird dann nicht gespeichert
+        if let Err(err) = profile.load_cycle_counters() {
+            warn!("Schaltspiele konnten nicht geladen werden, es wird nicht gespeichert: {}", err);
+        }
+
+        profile
+    }
+}
+
+impl FromStr for HardwareProfile {
+    type Err = Error;
+
+    /// Liest das Profil aus einem JSON String
+    fn from_str(json: &str) -> Result<Self> {
+        let mut profile: HardwareProfile = try!(serde_json::from_str(json));
+        try!(profile.validate());
+        try!(profile.load_cycle_counters());
+
+        Ok(profile)
+    }
+}
+
+impl HardwareProfile {
+    /// Lädt das Profil aus einer JSON Datei
+    ///
+    /// # Arguments
+    /// * `path`    - Pfad der Profil Datei
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = try!(File::open(path));
+        let mut profile: HardwareProfile = try!(serde_json::from_reader(file));
+        try!(profile.validate());
+        try!(profile.load_cycle_counters());
+
+        Ok(profile)
+    }
+
+    /// Lädt das Profil, das eingebaute Profil wird verwendet wenn die Datei nicht existiert
+    ///
+    /// # Arguments
+    /// * `path`    - Pfad der Profil Datei
+    pub fn load_or_default<P: AsRef<Path>>(path: P) -> Result<Self> {
+        match path.as_ref().exists() {
+            true => HardwareProfile::load(path),
+            false => Ok(HardwareProfile::default()),
+        }
+    }
+
+    /// Das Profil als JSON, z.B. als Vorlage für eine eigene Profil Datei
+    pub fn to_json(&self) -> Result<String> {
+        Ok(try!(serde_json::to_string_pretty(self)))
+    }
+
+    /// Lädt die gespeicherten Schaltspiel Zähler
+    fn load_cycle_counters(&mut self) -> Result<()> {
+        try!(self.leds.cycle_counter.load());
+        try!(self.relais.cycle_counter.load());
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        try!(self.leds.validate());
+        try!(self.relais.validate());
+
+        Ok(())
+    }
+}
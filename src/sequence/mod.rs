@@ -0,0 +1,18 @@
+//! Prüfsequenzen für die Endprüfung
+//!
+//! Eine Prüfsequenz beschreibt einen Test als JSON Datei: Ausgänge schalten, Muster abspielen,
+//! warten, den Bediener fragen und Modbus Register des Prüflings mit Grenzwerten vergleichen. So
+//! kann die QS die Tests ändern ohne das Test Tool neu zu übersetzen. Die Dateien liegen in
+//! `DEFAULT_SEQUENCE_DIR`, die GUI bietet sie in einem eigenen Tab an, auf der Kommandozeile
+//! läuft eine Datei mit `sequence DATEI`.
+pub mod modbus;
+pub mod runner;
+pub mod sequence;
+
+pub use self::modbus::{MockModbus, ModbusBus, ModbusConfig, RtuBus};
+pub use self::runner::{Event, SequenceRunner};
+pub use self::sequence::{Operator, Sequence, SequenceReport, Step, StepResult};
+
+
+/// Verzeichnis der Prüfsequenzen, wenn keins angegeben wurde
+pub const DEFAULT_SEQUENCE_DIR: &'static str = "/etc/xmz-mod-touch-test-tool/sequences";
@@ -0,0 +1,105 @@
+//! Modbus Zugriff der Prüfsequenzen
+//!
+//! Die `ReadModbus` Schritte lesen ein Holding Register des Prüflings über den `ModbusBus`. Auf
+//! dem Prüfplatz ist das der `RtuBus` an der Schnittstelle aus dem Hardware Profil, in Tests der
+//! `MockModbus`.
+//!
+//! # Examples
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::sequence::*;
+//!
+//! let mock = MockModbus::new();
+//! mock.set(1, 3, 420);
+//!
+//! let mut bus = mock.clone();
+//! assert_eq!(bus.read_register(1, 3).unwrap(), 420);
+//! assert!(bus.read_register(2, 3).is_err());
+//! ```
+use errors::*;
+use libmodbus_rs::{Modbus, ModbusClient, ModbusRTU};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+
+/// Serielle Schnittstelle für Modbus RTU
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ModbusConfig {
+    /// z.B. `/dev/ttyS1`
+    pub device: String,
+    pub baud: i32,
+    /// `'N'`, `'E'` oder `'O'`
+    pub parity: char,
+    pub data_bits: i32,
+    pub stop_bits: i32,
+}
+
+/// Lesender Zugriff auf die Register der Modbus Slaves
+pub trait ModbusBus {
+    /// Liest ein Holding Register
+    ///
+    /// # Arguments
+    /// * `slave`       - Slave ID des Prüflings
+    /// * `address`     - Adresse des Registers
+    fn read_register(&mut self, slave: u8, address: u16) -> Result<u16>;
+}
+
+/// Modbus RTU über die Schnittstelle aus dem Hardware Profil
+///
+/// Die Verbindung wird für jeden Lesezugriff geöffnet und wieder geschlossen. Der Bus hält so
+/// keinen libmodbus Kontext und kann an den Thread einer Prüfsequenz übergeben werden.
+#[derive(Clone, Debug)]
+pub struct RtuBus {
+    config: ModbusConfig,
+}
+
+impl RtuBus {
+    pub fn new(config: ModbusConfig) -> Self {
+        RtuBus {
+            config: config,
+        }
+    }
+}
+
+impl ModbusBus for RtuBus {
+    fn read_register(&mut self, slave: u8, address: u16) -> Result<u16> {
+        let mut modbus = try!(Modbus::new_rtu(&self.config.device, self.config.baud, self.config.parity,
+                                              self.config.data_bits, self.config.stop_bits));
+        try!(modbus.set_slave(slave));
+        try!(modbus.connect());
+        let mut value = [0u16; 1];
+        try!(modbus.read_registers(address, 1, &mut value));
+
+        Ok(value[0])
+    }
+}
+
+/// Modbus Slaves im Speicher, für Tests ohne Prüfling
+///
+/// Clones teilen sich die Register, so lassen sich die Werte auch nach der Übergabe an eine
+/// Prüfsequenz noch ändern. Nicht gesetzte Register antworten nicht.
+#[derive(Clone, Debug, Default)]
+pub struct MockModbus {
+    registers: Arc<Mutex<HashMap<(u8, u16), u16>>>,
+}
+
+impl MockModbus {
+    pub fn new() -> Self {
+        MockModbus::default()
+    }
+
+    /// Setzt den Wert eines Registers
+    pub fn set(&self, slave: u8, address: u16, value: u16) {
+        self.registers.lock().unwrap().insert((slave, address), value);
+    }
+}
+
+impl ModbusBus for MockModbus {
+    fn read_register(&mut self, slave: u8, address: u16) -> Result<u16> {
+        match self.registers.lock().unwrap().get(&(slave, address)) {
+            Some(value) => Ok(*value),
+            None => bail!("Modbus Slave {} antwortet nicht auf Register {}", slave, address),
+        }
+    }
+}
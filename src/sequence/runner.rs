@@ -0,0 +1,158 @@
+//! Prüfsequenz im Hintergrund, für die GUI
+//!
+//! Der `SequenceRunner` führt die Sequenz in einem eigenen Thread aus, so bleibt die GUI
+//! bedienbar. Fortschritt, Fragen an den Bediener und das Ergebnis kommen als `Event` und werden
+//! mit `try_event()` abgefragt. Die Antwort auf eine Frage geht mit `answer()` zurück.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//! use xmz_mod_touch_test_tool::sequence::*;
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let sequence: Sequence = r#"{
+//!     "name": "Relais",
+//!     "steps": [
+//!         { "Set": { "register": "Relais", "outputs": [2] } },
+//!         { "Ask": { "question": "Ist Relais 2 angezogen?" } }
+//!     ]
+//! }"#.parse().unwrap();
+//! let relais = Arc::new(Mutex::new(ShiftRegister::new(ShiftRegisterType::Simulation)));
+//! let leds = Arc::new(Mutex::new(ShiftRegister::new(ShiftRegisterType::Simulation)));
+//!
+//! let runner = SequenceRunner::start(sequence, relais.clone(), leds, None);
+//! let report = loop {
+//!     match runner.try_event() {
+//!         Some(Event::Question(_)) => runner.answer(false),
+//!         Some(Event::Finished(result)) => break result.unwrap(),
+//!         _ => ::std::thread::sleep(::std::time::Duration::from_millis(1)),
+//!     }
+//! };
+//! assert!(!report.passed);
+//! assert_eq!(relais.lock().unwrap().get(2).unwrap(), true);
+//! ```
+use errors::*;
+use sequence::modbus::ModbusBus;
+use sequence::sequence::{Operator, Sequence, SequenceReport, Step};
+use shift_register::ShiftRegister;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+
+/// Nachricht aus dem Thread der Sequenz
+#[derive(Debug)]
+pub enum Event {
+    /// Null basierter Index des Schritts, Anzahl der Schritte und Beschreibung
+    Step(usize, usize, String),
+    /// Ja/Nein Frage an den Bediener, wartet auf `SequenceRunner::answer()`
+    Question(String),
+    /// Die Sequenz ist beendet, danach kommt nix mehr
+    Finished(Result<SequenceReport>),
+}
+
+/// Führt eine `Sequence` in einem eigenen Thread aus, siehe Modul Dokumentation
+pub struct SequenceRunner {
+    cancel: Arc<AtomicBool>,
+    events: Receiver<Event>,
+    answers: Sender<bool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SequenceRunner {
+    /// Startet die Sequenz
+    ///
+    /// # Arguments
+    /// * `sequence`    - Auszuführende Sequenz
+    /// * `relais`      - Shift Register der Relais
+    /// * `leds`        - Shift Register der LED
+    /// * `modbus`      - Bus für die `ReadModbus` Schritte
+    pub fn start(sequence: Sequence, relais: Arc<Mutex<ShiftRegister>>, leds: Arc<Mutex<ShiftRegister>>,
+                 modbus: Option<Box<ModbusBus + Send>>) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (events_tx, events) = channel();
+        let (answers, answers_rx) = channel();
+
+        let thread = {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                let mut modbus = modbus;
+                let mut operator = ChannelOperator {
+                    total: sequence.steps.len(),
+                    events: events_tx.clone(),
+                    answers: answers_rx,
+                    cancel: cancel,
+                };
+                let result = sequence.run(&relais, &leds, &mut modbus, &mut operator);
+                let _ = events_tx.send(Event::Finished(result));
+            })
+        };
+
+        SequenceRunner {
+            cancel: cancel,
+            events: events,
+            answers: answers,
+            thread: Some(thread),
+        }
+    }
+
+    /// Nächste Nachricht aus dem Thread, `None` wenn gerade keine vorliegt
+    pub fn try_event(&self) -> Option<Event> {
+        self.events.try_recv().ok()
+    }
+
+    /// Beantwortet die letzte `Event::Question`
+    pub fn answer(&self, yes: bool) {
+        let _ = self.answers.send(yes);
+    }
+
+    /// Bricht die Sequenz ab und wartet auf das Ende des Threads
+    ///
+    /// Eine offene Frage gilt dabei als mit "Nein" beantwortet.
+    pub fn stop(mut self) -> Result<()> {
+        self.cancel.store(true, Ordering::SeqCst);
+        let _ = self.answers.send(false);
+        match self.thread.take().map(|thread| thread.join()) {
+            Some(Err(_)) => bail!("Thread der Prüfsequenz ist abgestürzt"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for SequenceRunner {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::SeqCst);
+        let _ = self.answers.send(false);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Bediener auf der anderen Seite der Channels
+struct ChannelOperator {
+    total: usize,
+    events: Sender<Event>,
+    answers: Receiver<bool>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Operator for ChannelOperator {
+    fn ask(&mut self, question: &str) -> bool {
+        if self.events.send(Event::Question(question.to_string())).is_err() {
+            return false;
+        }
+        // Ohne Antwort, z.B. weil der Runner verworfen wurde, gilt "Nein"
+        self.answers.recv().unwrap_or(false) && !self.cancelled()
+    }
+
+    fn step(&mut self, index: usize, step: &Step) {
+        let _ = self.events.send(Event::Step(index, self.total, step.description()));
+    }
+
+    fn cancelled(&mut self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,523 @@
+//! Aufbau und Ablauf einer Prüfsequenz
+//!
+//! Eine `Sequence` ist eine Liste von Schritten (`Step`), die der Reihe nach ausgeführt werden.
+//! Schlägt ein Schritt fehl, z.B. weil der Bediener eine Frage mit "Nein" beantwortet oder ein
+//! Modbus Wert außerhalb der Grenzwerte liegt, endet die Sequenz dort und gilt als nicht bestanden.
+//! Fehler der Hardware beenden die Sequenz mit einem `Err`.
+//!
+//! Endet die Sequenz mit einem Fehler oder wird sie abgebrochen, bringt `run()` LED und Relais in
+//! den sicheren Zustand (`ShiftRegister::enter_safe_state()`), damit keine Ausgänge an bleiben.
+//! Eine bis zum Ende gelaufene Sequenz lässt die Ausgänge so, wie der letzte Schritt sie gesetzt hat.
+//!
+//! Vor dem ersten Schritt wird die Sequenz gegen die Hardware geprüft: Ausgangsnummern die es im
+//! Shift Register nicht gibt und `ReadModbus` Schritte ohne Modbus Schnittstelle lehnt `run()` ab,
+//! ohne einen Schritt auszuführen. Mit `validate_profile()` lässt sich das schon vorher gegen ein
+//! `HardwareProfile` prüfen.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//! use xmz_mod_touch_test_tool::sequence::*;
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let sequence: Sequence = r#"{
+//!     "name": "Endprüfung",
+//!     "steps": [
+//!         { "Set": { "register": "Relais", "outputs": [1, 3] } },
+//!         { "Ask": { "question": "Sind Relais 1 und 3 angezogen?" } },
+//!         { "Random": { "register": "Leds", "seed": 42 } },
+//!         { "Wait": { "ms": 1 } },
+//!         { "ReadModbus": { "name": "CO", "slave": 1, "address": 3, "min": 0, "max": 10 } },
+//!         { "Reset": {} }
+//!     ]
+//! }"#.parse().unwrap();
+//!
+//! let relais = Arc::new(Mutex::new(ShiftRegister::new(ShiftRegisterType::Simulation)));
+//! let leds = Arc::new(Mutex::new(ShiftRegister::new(ShiftRegisterType::Simulation)));
+//! let mock = MockModbus::new();
+//! mock.set(1, 3, 4);
+//! let mut modbus: Option<Box<ModbusBus + Send>> = Some(Box::new(mock.clone()));
+//!
+//! // Bediener der jede Frage mit "Ja" beantwortet
+//! struct Yes;
+//! impl Operator for Yes {
+//!     fn ask(&mut self, _: &str) -> bool { true }
+//! }
+//!
+//! let report = sequence.run(&relais, &leds, &mut modbus, &mut Yes).unwrap();
+//! assert!(report.passed);
+//! assert_eq!(report.steps.len(), 6);
+//! assert_eq!(report.steps[2].random_test.as_ref().map(|test| test.seed), Some(42));
+//! assert_eq!(report.steps[4].value, Some(4));
+//!
+//! // Außerhalb der Grenzwerte endet die Sequenz beim Modbus Schritt
+//! mock.set(1, 3, 11);
+//! let report = sequence.run(&relais, &leds, &mut modbus, &mut Yes).unwrap();
+//! assert!(!report.passed);
+//! assert_eq!(report.steps.len(), 5);
+//! ```
+//!
+//! Prüfung gegen das Hardware Profil und Abbruch während eines Musters:
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//! use std::time::{Duration, Instant};
+//! use xmz_mod_touch_test_tool::profile::HardwareProfile;
+//! use xmz_mod_touch_test_tool::sequence::*;
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let profile = HardwareProfile {
+//!     leds: ShiftRegister::new(ShiftRegisterType::Simulation),
+//!     relais: ShiftRegister::new(ShiftRegisterType::Simulation),
+//!     modbus: None,
+//! };
+//! let sequence: Sequence = r#"{ "name": "Relais", "steps": [{ "Set": { "register": "Relais", "outputs": [65] } }] }"#
+//!     .parse().unwrap();
+//! assert!(sequence.validate_profile(&profile).is_err());
+//! let sequence: Sequence = r#"{ "name": "CO", "steps": [{ "ReadModbus": { "name": "CO", "slave": 1, "address": 3, "min": 0, "max": 10 } }] }"#
+//!     .parse().unwrap();
+//! assert!(sequence.validate_profile(&profile).is_err());
+//!
+//! // `run()` prüft ebenfalls, bevor der erste Schritt ausgeführt wird
+//! let relais = Arc::new(Mutex::new(profile.relais));
+//! let leds = Arc::new(Mutex::new(profile.leds));
+//! struct Cancel(bool);
+//! impl Operator for Cancel {
+//!     fn ask(&mut self, _: &str) -> bool { true }
+//!     fn step(&mut self, _: usize, _: &Step) { self.0 = true; }
+//!     fn cancelled(&mut self) -> bool { self.0 }
+//! }
+//! assert!(sequence.run(&relais, &leds, &mut None, &mut Cancel(false)).is_err());
+//!
+//! // Das Muster würde über eine Minute laufen, der Abbruch wirkt aber sofort
+//! let sequence: Sequence = r#"{ "name": "Muster", "steps": [
+//!     { "Pattern": { "register": "Leds", "pattern": "Chase", "interval_ms": 1000 } }
+//! ] }"#.parse().unwrap();
+//! relais.lock().unwrap().set(1).unwrap();
+//! let start = Instant::now();
+//! let report = sequence.run(&relais, &leds, &mut None, &mut Cancel(false)).unwrap();
+//! assert!(start.elapsed() < Duration::from_millis(500));
+//! assert!(report.cancelled);
+//! assert!(!report.passed);
+//! // Nach dem Abbruch sind alle Ausgänge aus
+//! assert_eq!(relais.lock().unwrap().get(1).unwrap(), false);
+//! assert!((1..9).all(|num| !leds.lock().unwrap().get(num).unwrap()));
+//! ```
+use errors::*;
+use profile::{HardwareProfile, Register};
+use sequence::modbus::ModbusBus;
+use serde_json;
+use shift_register::*;
+use std::fmt;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+
+/// Abfrage Intervall für den Abbruch während `Wait` und `Pattern`
+const CANCEL_POLL_MS: u64 = 10;
+
+/// Bringt beim Drop LED und Relais in den sicheren Zustand, solange `armed` gesetzt ist
+///
+/// Damit bleiben nach einem Fehler, Abbruch oder Panic in `Sequence::run()` keine Ausgänge an.
+struct SafeStateGuard<'a> {
+    registers: [&'a Arc<Mutex<ShiftRegister>>; 2],
+    armed: bool,
+}
+
+impl<'a> Drop for SafeStateGuard<'a> {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        for register in self.registers.iter() {
+            let mut register = match register.lock() {
+                Ok(register) => register,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            if let Err(err) = register.enter_safe_state() {
+                warn!("Sicherer Zustand nach Ende der Sequenz fehlgeschlagen: {}", err);
+            }
+        }
+    }
+}
+
+/// Ein Schritt einer Prüfsequenz
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum Step {
+    /// Schaltet die Ausgänge ein, die übrigen bleiben wie sie sind
+    Set { register: Register, outputs: Vec<u64> },
+    /// Schaltet die Ausgänge aus, die übrigen bleiben wie sie sind
+    Clear { register: Register, outputs: Vec<u64> },
+    /// Schaltet alle Ausgänge aus, ohne `register` die von LED und Relais
+    Reset { register: Option<Register> },
+    /// Spielt das Muster einmal ab
+    Pattern { register: Register, pattern: Pattern, interval_ms: u64 },
+    /// Zufallstest, mit `seed` wird ein früherer Test wiederholt
+    Random { register: Register, seed: Option<u64> },
+    /// Wartet, z.B. bis ein Relais sicher angezogen ist
+    Wait { ms: u64 },
+    /// Ja/Nein Frage an den Bediener, "Nein" lässt den Schritt fehlschlagen
+    Ask { question: String },
+    /// Liest ein Holding Register des Prüflings, der Wert muss zwischen `min` und `max` liegen
+    ReadModbus { name: String, slave: u8, address: u16, min: u16, max: u16 },
+}
+
+impl Step {
+    /// Kurze Beschreibung für Anzeige und Log
+    pub fn description(&self) -> String {
+        match *self {
+            Step::Set { register, ref outputs } => format!("{:?} {} einschalten", register, nums(outputs)),
+            Step::Clear { register, ref outputs } => format!("{:?} {} ausschalten", register, nums(outputs)),
+            Step::Reset { register: Some(register) } => format!("{:?} ausschalten", register),
+            Step::Reset { register: None } => "Relais und Leds ausschalten".to_string(),
+            Step::Pattern { register, ref pattern, .. } => format!("{:?} Muster {:?}", register, pattern),
+            Step::Random { register, .. } => format!("{:?} Zufallstest", register),
+            Step::Wait { ms } => format!("{} ms warten", ms),
+            Step::Ask { ref question } => question.clone(),
+            Step::ReadModbus { ref name, min, max, .. } => format!("{} lesen, {} bis {}", name, min, max),
+        }
+    }
+}
+
+/// Bediener am Prüfplatz
+pub trait Operator {
+    /// Stellt eine Ja/Nein Frage, siehe `Step::Ask`
+    fn ask(&mut self, question: &str) -> bool;
+
+    /// Wird vor jedem Schritt aufgerufen, z.B. für eine Fortschrittsanzeige
+    ///
+    /// # Arguments
+    /// * `index`   - Null basierter Index des Schritts
+    /// * `step`    - Der Schritt der gleich ausgeführt wird
+    fn step(&mut self, _index: usize, _step: &Step) {}
+
+    /// `true` wenn die Sequenz abgebrochen werden soll, wird vor jedem Schritt und während
+    /// `Wait` und `Pattern` abgefragt
+    fn cancelled(&mut self) -> bool {
+        false
+    }
+}
+
+/// Ergebnis eines Schritts
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize)]
+pub struct StepResult {
+    /// Null basierter Index des Schritts
+    pub index: usize,
+    pub description: String,
+    pub passed: bool,
+    /// Gelesener Wert eines `ReadModbus` Schritts
+    pub value: Option<u16>,
+    /// Seed und Frames eines `Random` Schritts, damit der Test wiederholt werden kann
+    pub random_test: Option<RandomTest>,
+}
+
+/// Ergebnis einer Prüfsequenz, ein Eintrag pro ausgeführtem Schritt
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize)]
+pub struct SequenceReport {
+    pub name: String,
+    pub passed: bool,
+    /// `true` wenn die Sequenz vom Bediener abgebrochen wurde
+    pub cancelled: bool,
+    pub steps: Vec<StepResult>,
+}
+
+impl fmt::Display for SequenceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = match (self.passed, self.cancelled) {
+            (_, true) => "abgebrochen",
+            (true, false) => "bestanden",
+            (false, false) => "nicht bestanden",
+        };
+        try!(writeln!(f, "{}: {}", self.name, result));
+        for step in &self.steps {
+            try!(write!(f, "{}. {}: {}", step.index + 1, step.description, if step.passed { "OK" } else { "Fehler" }));
+            if let Some(value) = step.value {
+                try!(write!(f, ", Wert {}", value));
+            }
+            if let Some(ref random_test) = step.random_test {
+                try!(write!(f, ", {}", random_test));
+            }
+            try!(writeln!(f, ""));
+        }
+
+        Ok(())
+    }
+}
+
+/// Prüfsequenz, siehe Modul Dokumentation
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct Sequence {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub steps: Vec<Step>,
+}
+
+impl FromStr for Sequence {
+    type Err = Error;
+
+    /// Liest die Sequenz aus einem JSON String
+    fn from_str(json: &str) -> Result<Self> {
+        let sequence: Sequence = try!(serde_json::from_str(json));
+        try!(sequence.validate());
+
+        Ok(sequence)
+    }
+}
+
+impl Sequence {
+    /// Lädt die Sequenz aus einer JSON Datei
+    ///
+    /// # Arguments
+    /// * `path`    - Pfad der Sequenz Datei
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = try!(File::open(path));
+        let sequence: Sequence = try!(serde_json::from_reader(file));
+        try!(sequence.validate());
+
+        Ok(sequence)
+    }
+
+    /// Alle Sequenz Dateien (`*.json`) im Verzeichnis, sortiert nach Namen
+    ///
+    /// Existiert das Verzeichnis nicht, gibt es eben keine Sequenzen.
+    pub fn list<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>> {
+        if !dir.as_ref().is_dir() {
+            return Ok(vec![]);
+        }
+        let mut paths = vec![];
+        for entry in try!(fs::read_dir(dir)) {
+            let path = try!(entry).path();
+            if path.extension().map(|extension| extension == "json").unwrap_or(false) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        Ok(paths)
+    }
+
+    /// Führt die Sequenz aus
+    ///
+    /// # Arguments
+    /// * `relais`      - Shift Register der Relais
+    /// * `leds`        - Shift Register der LED
+    /// * `modbus`      - Bus für die `ReadModbus` Schritte, ohne Bus schlagen diese mit einem Fehler fehl
+    /// * `operator`    - Bediener für die `Ask` Schritte und den Abbruch
+    pub fn run(&self, relais: &Arc<Mutex<ShiftRegister>>, leds: &Arc<Mutex<ShiftRegister>>,
+               modbus: &mut Option<Box<ModbusBus + Send>>, operator: &mut Operator) -> Result<SequenceReport> {
+        {
+            let (relais, leds) = (relais.lock().unwrap(), leds.lock().unwrap());
+            try!(self.validate_hardware(relais.outputs, leds.outputs, modbus.is_some()));
+        }
+
+        // Endet der Lauf mit einem Fehler oder Abbruch, gehen LED und Relais in den sicheren Zustand
+        let mut guard = SafeStateGuard { registers: [relais, leds], armed: true };
+        let mut report = SequenceReport {
+            name: self.name.clone(),
+            passed: true,
+            cancelled: false,
+            steps: vec![],
+        };
+
+        for (index, step) in self.steps.iter().enumerate() {
+            if operator.cancelled() {
+                report.cancelled = true;
+                break;
+            }
+            operator.step(index, step);
+            info!("{}: Schritt {}, {}", self.name, index + 1, step.description());
+
+            let result = try!(execute(step, relais, leds, modbus, operator)
+                .chain_err(|| format!("{}: Schritt {} ({}) fehlgeschlagen", self.name, index + 1, step.description())));
+            let passed = result.passed && !result.cancelled;
+            report.cancelled = result.cancelled;
+            report.steps.push(StepResult {
+                index: index,
+                description: step.description(),
+                passed: passed,
+                value: result.value,
+                random_test: result.random_test,
+            });
+            if !passed {
+                break;
+            }
+        }
+        report.passed = !report.cancelled && report.steps.iter().all(|step| step.passed);
+        guard.armed = report.cancelled;
+        match report.passed {
+            true => info!("{}: bestanden", self.name),
+            false => warn!("{}", report.to_string().trim_right().replace('\n', ", ")),
+        }
+
+        Ok(report)
+    }
+
+    /// Prüft ob die Sequenz zum Hardware Profil passt, siehe Modul Dokumentation
+    pub fn validate_profile(&self, profile: &HardwareProfile) -> Result<()> {
+        self.validate_hardware(profile.relais.outputs, profile.leds.outputs, profile.modbus.is_some())
+    }
+
+    /// Prüft die Ausgangsnummern gegen die vorhandenen Ausgänge und ob es für `ReadModbus` eine
+    /// Modbus Schnittstelle gibt
+    fn validate_hardware(&self, relais_outputs: usize, leds_outputs: usize, modbus: bool) -> Result<()> {
+        for (index, step) in self.steps.iter().enumerate() {
+            match *step {
+                Step::Set { register, ref outputs } |
+                Step::Clear { register, ref outputs } => {
+                    let available = match register {
+                        Register::Relais => relais_outputs,
+                        Register::Leds => leds_outputs,
+                    };
+                    if let Some(num) = outputs.iter().find(|num| **num == 0 || **num > available as u64) {
+                        bail!(ErrorKind::InvalidSequence(format!("{}: Schritt {}, {:?} hat keinen Ausgang {} (1 bis {})",
+                                                                 self.name, index + 1, register, num, available)));
+                    }
+                },
+                Step::ReadModbus { .. } if !modbus => {
+                    bail!(ErrorKind::InvalidSequence(format!("{}: Schritt {}, keine Modbus Schnittstelle im Hardware Profil",
+                                                             self.name, index + 1)));
+                },
+                _ => {},
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prüft ob die Sequenz in sich stimmig ist
+    fn validate(&self) -> Result<()> {
+        if self.steps.is_empty() {
+            bail!(ErrorKind::InvalidSequence(format!("{}: keine Schritte", self.name)));
+        }
+        for (index, step) in self.steps.iter().enumerate() {
+            if let Step::ReadModbus { min, max, .. } = *step {
+                if min > max {
+                    bail!(ErrorKind::InvalidSequence(format!("{}: Schritt {}, min {} ist größer als max {}",
+                                                             self.name, index + 1, min, max)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Ergebnis eines Schritts, ohne Index und Beschreibung
+struct Outcome {
+    passed: bool,
+    /// `true` wenn der Schritt vom Bediener abgebrochen wurde
+    cancelled: bool,
+    value: Option<u16>,
+    random_test: Option<RandomTest>,
+}
+
+impl Outcome {
+    fn passed(passed: bool) -> Self {
+        Outcome {
+            passed: passed,
+            cancelled: false,
+            value: None,
+            random_test: None,
+        }
+    }
+
+    fn cancelled() -> Self {
+        Outcome {
+            passed: false,
+            cancelled: true,
+            value: None,
+            random_test: None,
+        }
+    }
+}
+
+fn execute(step: &Step, relais: &Arc<Mutex<ShiftRegister>>, leds: &Arc<Mutex<ShiftRegister>>,
+           modbus: &mut Option<Box<ModbusBus + Send>>, operator: &mut Operator) -> Result<Outcome> {
+    let select = |register: Register| match register {
+        Register::Relais => relais,
+        Register::Leds => leds,
+    };
+
+    match *step {
+        Step::Set { register, ref outputs } => {
+            let mut register = select(register).lock().unwrap();
+            let mut transaction = register.transaction();
+            for num in outputs {
+                try!(transaction.set(*num));
+            }
+            try!(transaction.commit());
+        },
+        Step::Clear { register, ref outputs } => {
+            let mut register = select(register).lock().unwrap();
+            let mut transaction = register.transaction();
+            for num in outputs {
+                try!(transaction.clear(*num));
+            }
+            try!(transaction.commit());
+        },
+        Step::Reset { register: Some(register) } => try!(select(register).lock().unwrap().reset()),
+        Step::Reset { register: None } => {
+            try!(relais.lock().unwrap().reset());
+            try!(leds.lock().unwrap().reset());
+        },
+        Step::Pattern { register, ref pattern, interval_ms } => {
+            let runner = PatternRunner::start(select(register).clone(), pattern.clone(), Duration::from_millis(interval_ms), false);
+            while !runner.is_finished() {
+                if operator.cancelled() {
+                    try!(runner.stop());
+                    return Ok(Outcome::cancelled());
+                }
+                thread::sleep(Duration::from_millis(CANCEL_POLL_MS));
+            }
+            try!(runner.stop());
+        },
+        Step::Random { register, seed } => {
+            let random_test = try!(select(register).lock().unwrap().test_random(seed));
+            return Ok(Outcome {
+                passed: true,
+                cancelled: false,
+                value: None,
+                random_test: Some(random_test),
+            });
+        },
+        Step::Wait { ms } => {
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_millis(ms) {
+                if operator.cancelled() {
+                    return Ok(Outcome::cancelled());
+                }
+                thread::sleep(Duration::from_millis(CANCEL_POLL_MS));
+            }
+        },
+        Step::Ask { ref question } => return Ok(Outcome::passed(operator.ask(question))),
+        Step::ReadModbus { slave, address, min, max, .. } => {
+            let value = match *modbus {
+                Some(ref mut modbus) => try!(modbus.read_register(slave, address)),
+                None => bail!("Keine Modbus Schnittstelle im Hardware Profil"),
+            };
+            return Ok(Outcome {
+                passed: value >= min && value <= max,
+                cancelled: false,
+                value: Some(value),
+                random_test: None,
+            });
+        },
+    }
+
+    Ok(Outcome::passed(true))
+}
+
+/// Ausgangsnummern für die Beschreibung, z.B. "1, 3"
+fn nums(outputs: &[u64]) -> String {
+    outputs.iter().map(|num| num.to_string()).collect::<Vec<String>>().join(", ")
+}
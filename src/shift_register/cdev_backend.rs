@@ -0,0 +1,167 @@
+//! GPIO Backend über das Character Device Interface des Linux Kernels (`/dev/gpiochipN`)
+//!
+//! Neuere Kernel bzw. Board Images werden ohne das (veraltete) sysfs GPIO Interface ausgeliefert.
+//! Dieses Backend fordert die Pins über die Line Request API von `gpio-cdev` an. Die Pins werden
+//! dabei nicht über ihre globale Kernel Nummer, sondern über Chip und Offset adressiert.
+//!
+//! Ohne weitere Angaben sind die Pin Nummern des `ShiftRegister` die Offsets auf dem Chip, der
+//! `CdevBackend::new()` übergeben wurde. Mit `map_pin()` kann jeder Pin auf einen beliebigen
+//! Chip und Offset gelegt werden.
+//!
+//! # Examples
+//!
+//! Getestet werden kann das Backend mit dem `gpio-mockup` (bzw. `gpio-sim`) Modul des Kernels.
+//!
+//! ```text
+//! sudo modprobe gpio-mockup gpio_mockup_ranges=-1,8
+//! XMZ_GPIO_MOCKUP_CHIP=/dev/gpiochip1 cargo test --doc cdev_backend
+//! ```
+//!
+//! Ist `XMZ_GPIO_MOCKUP_CHIP` nicht gesetzt, wird das Beispiel übersprungen.
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let chip = match ::std::env::var("XMZ_GPIO_MOCKUP_CHIP") {
+//!     Ok(chip) => chip,
+//!     Err(_) => return,
+//! };
+//!
+//! let mut backend = CdevBackend::new(&chip).unwrap();
+//! backend.set_direction(7, Direction::Out).unwrap();
+//! backend.set_value(7, 1).unwrap();
+//! assert_eq!(backend.get_value(7).unwrap(), 1);
+//! backend.unexport(7).unwrap();
+//!
+//! // OE, DS, CLOCK und LATCH auf die Offsets 0 bis 3 des Mockup Chips legen
+//! let mut leds = ShiftRegister::with_backend(ShiftRegisterType::LED, Box::new(backend));
+//! leds.oe_pin = Some(0);
+//! leds.ds_pin = Some(1);
+//! leds.clock_pin = Some(2);
+//! leds.latch_pin = Some(3);
+//! leds.set(1).unwrap();
+//! leds.reset().unwrap();
+//! ```
+use errors::*;
+use gpio_cdev::{Chip, LineHandle, LineRequestFlags};
+use shift_register::gpio_backend::{Direction, GpioBackend};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+
+/// Name unter dem die Lines im Kernel als belegt geführt werden
+const CONSUMER: &'static str = "xmz-mod-touch-test-tool";
+
+/// Pin der auf einem eigenen Chip und Offset liegt, siehe `GpioDriver::Cdev` und `map_pin()`
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct CdevLine {
+    /// Pin Nummer wie sie im `ShiftRegister` verwendet wird (`oe_pin`, `ds_pin`, ...)
+    pub pin: u64,
+    /// Pfad des Character Devices, z.B. `/dev/gpiochip1`
+    pub chip: String,
+    /// Offset der Line auf diesem Chip
+    pub offset: u32,
+}
+
+/// Spricht die Pins als Chip + Offset über `/dev/gpiochipN` an
+#[derive(Debug)]
+pub struct CdevBackend {
+    /// Chip auf dem nicht gemappte Pins liegen
+    default_chip: PathBuf,
+    /// Explizit gemappte Pins, Pin Nummer -> (Chip, Offset)
+    mapping: HashMap<u64, (PathBuf, u32)>,
+    chips: HashMap<PathBuf, Chip>,
+    /// Angeforderte Lines, die Line bleibt belegt solange der Handle existiert
+    handles: HashMap<u64, LineHandle>,
+}
+
+impl CdevBackend {
+    /// Öffnet den GPIO Chip, die Pin Nummern sind Offsets auf diesem Chip
+    ///
+    /// # Arguments
+    /// * `path`    - Pfad des Character Devices, z.B. `/dev/gpiochip0`
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let chip = try!(Chip::new(&path));
+        let mut chips = HashMap::new();
+        chips.insert(path.clone(), chip);
+
+        Ok(CdevBackend {
+            default_chip: path,
+            mapping: HashMap::new(),
+            chips: chips,
+            handles: HashMap::new(),
+        })
+    }
+
+    /// Legt einen Pin auf einen Offset eines (weiteren) Chips
+    ///
+    /// # Arguments
+    /// * `pin`     - Pin Nummer wie sie im `ShiftRegister` verwendet wird
+    /// * `path`    - Pfad des Character Devices, z.B. `/dev/gpiochip1`
+    /// * `offset`  - Offset der Line auf diesem Chip
+    pub fn map_pin<P: AsRef<Path>>(&mut self, pin: u64, path: P, offset: u32) -> Result<()> {
+        let path = path.as_ref().to_path_buf();
+        if !self.chips.contains_key(&path) {
+            let chip = try!(Chip::new(&path));
+            self.chips.insert(path.clone(), chip);
+        }
+        self.mapping.insert(pin, (path, offset));
+
+        Ok(())
+    }
+
+    /// Fordert die Line des Pins neu an, ein bestehender Handle wird vorher freigegeben
+    fn request(&mut self, pin: u64, flags: LineRequestFlags, default: u8) -> Result<()> {
+        self.handles.remove(&pin);
+
+        let (path, offset) = match self.mapping.get(&pin) {
+            Some(&(ref path, offset)) => (path.clone(), offset),
+            None => (self.default_chip.clone(), pin as u32),
+        };
+        let chip = self.chips.get_mut(&path).expect("GPIO Chip wurde beim Mapping geöffnet");
+        let line = try!(chip.get_line(offset));
+        let handle = try!(line.request(flags, default, CONSUMER));
+        self.handles.insert(pin, handle);
+
+        Ok(())
+    }
+}
+
+impl GpioBackend for CdevBackend {
+    /// Lines müssen nicht exportiert werden, sie werden mit `set_direction()` angefordert
+    fn export(&mut self, _pin: u64) -> Result<()> {
+        Ok(())
+    }
+
+    fn unexport(&mut self, pin: u64) -> Result<()> {
+        self.handles.remove(&pin);
+
+        Ok(())
+    }
+
+    fn set_direction(&mut self, pin: u64, direction: Direction) -> Result<()> {
+        match direction {
+            Direction::In => self.request(pin, LineRequestFlags::INPUT, 0),
+            Direction::Out => self.request(pin, LineRequestFlags::OUTPUT, 0),
+        }
+    }
+
+    fn set_value(&mut self, pin: u64, value: u8) -> Result<()> {
+        if !self.handles.contains_key(&pin) {
+            return self.request(pin, LineRequestFlags::OUTPUT, value);
+        }
+        try!(self.handles[&pin].set_value(value));
+
+        Ok(())
+    }
+
+    fn get_value(&mut self, pin: u64) -> Result<u8> {
+        if !self.handles.contains_key(&pin) {
+            try!(self.request(pin, LineRequestFlags::INPUT, 0));
+        }
+
+        Ok(try!(self.handles[&pin].get_value()))
+    }
+}
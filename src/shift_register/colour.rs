@@ -0,0 +1,58 @@
+//! Farbe eines Ausgangs in der GUI
+//!
+//! Die Farben werden im Hardware Profil unter `colours` hinterlegt, `colours[0]` gehört zu
+//! Ausgang 1. Die GUI färbt damit die Buttons der eingeschalteten Ausgänge und die LED der
+//! virtuellen Front.
+//!
+//! # Examples
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let leds = ShiftRegister::new(ShiftRegisterType::LED);
+//! assert_eq!(leds.colour(1), Some(Colour::Green));
+//! assert_eq!(leds.colour(1).unwrap().css(), "green");
+//!
+//! let relais = ShiftRegister::new(ShiftRegisterType::RELAIS);
+//! assert_eq!(relais.colour(1), None);
+//! ```
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum Colour {
+    Green,
+    Yellow,
+    Red,
+    Blue,
+    White,
+}
+
+impl Colour {
+    /// Alle Farben, z.B. für die CSS Regeln der GUI
+    pub fn all() -> Vec<Colour> {
+        vec![Colour::Green, Colour::Yellow, Colour::Red, Colour::Blue, Colour::White]
+    }
+
+    /// Name der Farbe in Kleinbuchstaben, passend für CSS Klassen
+    pub fn css(&self) -> &'static str {
+        match *self {
+            Colour::Green => "green",
+            Colour::Yellow => "yellow",
+            Colour::Red => "red",
+            Colour::Blue => "blue",
+            Colour::White => "white",
+        }
+    }
+
+    /// Rot, Grün und Blau Anteil von `0.0` bis `1.0`, so wie die LED aussieht
+    pub fn rgb(&self) -> (f64, f64, f64) {
+        match *self {
+            Colour::Green => (0.1, 0.9, 0.2),
+            Colour::Yellow => (1.0, 0.8, 0.0),
+            Colour::Red => (1.0, 0.1, 0.1),
+            Colour::Blue => (0.2, 0.4, 1.0),
+            Colour::White => (1.0, 1.0, 1.0),
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Schaltspiel Zähler der Ausgänge
+//!
+//! Die Relais der Prüfadapter verschleißen bei tausenden Testläufen. Das Relais `ShiftRegister`
+//! zählt deshalb für jeden Ausgang die Schaltspiele (Wechsel von aus nach an). Ist im Hardware
+//! Profil unter `cycle_counter` ein `path` angegeben, werden die Zähler dort als JSON gespeichert
+//! und beim nächsten Start wieder geladen. Ausgänge die die `warning_threshold` erreicht haben,
+//! werden von `worn()` geliefert. Nach der Wartung des Prüfadapters setzt `reset()` alle Zähler
+//! zurück.
+//!
+//! Gezählt wird im Speicher. Gespeichert wird höchstens alle `SAVE_INTERVAL_SECS` Sekunden sowie
+//! mit `flush()`, das das `ShiftRegister` beim Schließen der Session, im sicheren Zustand und beim
+//! Drop aufruft. Fehler beim Speichern werden nur geloggt, die Ausgänge sind dann ja bereits
+//! geschaltet.
+//!
+//! # Examples
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let mut relais = ShiftRegister::new(ShiftRegisterType::RELAIS);
+//! relais.gpio = GpioDriver::None;
+//! relais.cycle_counter.warning_threshold = Some(2);
+//!
+//! relais.set(1).unwrap();
+//! relais.set(2).unwrap();
+//! relais.clear(1).unwrap();
+//! relais.set(1).unwrap();
+//! assert_eq!(relais.cycle_counter.cycles(1), 2);
+//! assert_eq!(relais.cycle_counter.cycles(2), 1);
+//! assert_eq!(relais.cycle_counter.worn(), vec![1]);
+//!
+//! relais.cycle_counter.reset().unwrap();
+//! assert_eq!(relais.cycle_counter.cycles(1), 0);
+//!
+//! // Die LED verschleißen nicht, hier wird nicht gezählt
+//! let mut leds = ShiftRegister::new(ShiftRegisterType::LED);
+//! leds.gpio = GpioDriver::None;
+//! leds.set(1).unwrap();
+//! assert_eq!(leds.cycle_counter.cycles(1), 0);
+//! ```
+//!
+//! Spätestens beim Schließen der Session stehen die Zähler in der Datei:
+//!
+//! ```
+//! use std::env;
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let path = env::temp_dir().join("xmz-cycle-counter-doctest.json");
+//! let _ = std::fs::remove_file(&path);
+//!
+//! let mock = RecordingBackend::new();
+//! let mut relais = ShiftRegister::with_backend(ShiftRegisterType::RELAIS, Box::new(mock));
+//! relais.cycle_counter.path = Some(path.clone());
+//! for _ in 0..3 {
+//!     relais.set(2).unwrap();
+//!     relais.clear(2).unwrap();
+//! }
+//! relais.close_session().unwrap();
+//!
+//! let mut counter = CycleCounter::default();
+//! counter.path = Some(path);
+//! counter.load().unwrap();
+//! assert_eq!(counter.cycles(2), 3);
+//! ```
+use errors::*;
+use serde_json;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+
+/// Mindestabstand in Sekunden zwischen zwei Speichervorgängen aus `count()`
+pub const SAVE_INTERVAL_SECS: u64 = 5;
+
+/// Schaltspiele pro Ausgang, siehe Modul Dokumentation
+#[derive(Clone, Debug, Default, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct CycleCounter {
+    /// Datei in der die Zähler gespeichert werden, ohne Pfad wird nur im Speicher gezählt
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// Ab dieser Anzahl Schaltspiele gilt ein Ausgang als verschlissen
+    #[serde(default)]
+    pub warning_threshold: Option<u64>,
+    /// Schaltspiele, `counts[0]` gehört zu Ausgang 1
+    #[serde(skip_serializing, skip_deserializing)]
+    counts: Vec<u64>,
+    /// `true` sobald die Zähler aus `path` geladen wurden
+    #[serde(skip_serializing, skip_deserializing)]
+    loaded: bool,
+    /// `true` wenn sich die Zähler seit dem letzten Speichern geändert haben
+    #[serde(skip_serializing, skip_deserializing)]
+    dirty: bool,
+    /// Zeitpunkt des letzten Speicherns aus `count()`
+    #[serde(skip_serializing, skip_deserializing)]
+    last_save: Option<Instant>,
+}
+
+impl CycleCounter {
+    /// Lädt die Zähler aus `path`, existiert die Datei noch nicht wird bei Null begonnen
+    pub fn load(&mut self) -> Result<()> {
+        if let Some(ref path) = self.path {
+            if path.exists() {
+                let file = try!(File::open(path));
+                self.counts = try!(serde_json::from_reader(file));
+            }
+        }
+        self.loaded = true;
+
+        Ok(())
+    }
+
+    /// Speichert die Zähler nach `path`, ohne Pfad passiert nix
+    ///
+    /// Es wird zuerst in eine temporäre Datei geschrieben und diese dann umbenannt, so bleibt bei
+    /// einem Absturz immer ein vollständiger Stand erhalten. Fehlt das Verzeichnis, wird es
+    /// angelegt.
+    pub fn save(&self) -> Result<()> {
+        if let Some(ref path) = self.path {
+            if let Some(dir) = path.parent() {
+                try!(fs::create_dir_all(dir));
+            }
+            let tmp_path = path.with_extension("tmp");
+            {
+                let file = try!(File::create(&tmp_path));
+                try!(serde_json::to_writer(file, &self.counts));
+            }
+            try!(fs::rename(&tmp_path, path));
+        }
+
+        Ok(())
+    }
+
+    /// Zählt alle Ausgänge die von `old` nach `new` eingeschaltet wurden
+    ///
+    /// Gespeichert wird nur, wenn das letzte Speichern länger als `SAVE_INTERVAL_SECS` her ist.
+    ///
+    /// # Arguments
+    /// * `old`     - Bisheriger Zustand im Aufbau von `ShiftRegister.data`
+    /// * `new`     - Neuer Zustand im Aufbau von `ShiftRegister.data`
+    pub fn count(&mut self, old: &[u8], new: &[u8]) {
+        // Geladen wird nur vor dem ersten Zählen, sonst wären die Zähler im Speicher verloren
+        if !self.loaded && !self.dirty {
+            if let Err(err) = self.load() {
+                warn!("Schaltspiele konnten nicht geladen werden, es wird nicht gespeichert: {}", err);
+            }
+        }
+
+        for (chip, byte) in new.iter().enumerate() {
+            let rising = byte & !old.get(chip).cloned().unwrap_or(0);
+            for bit in 0..8 {
+                if rising & (1 << bit) != 0 {
+                    let index = chip * 8 + bit;
+                    if self.counts.len() <= index {
+                        self.counts.resize(index + 1, 0);
+                    }
+                    self.counts[index] += 1;
+                    self.dirty = true;
+                }
+            }
+        }
+        let due = match self.last_save {
+            Some(last_save) => last_save.elapsed() >= Duration::from_secs(SAVE_INTERVAL_SECS),
+            None => true,
+        };
+        if due {
+            self.flush();
+        }
+    }
+
+    /// Speichert geänderte Zähler, Fehler werden nur geloggt
+    ///
+    /// Konnten die gespeicherten Zähler nicht geladen werden, wird die Datei nicht überschrieben.
+    pub fn flush(&mut self) {
+        if !self.dirty || !self.loaded {
+            return;
+        }
+        self.last_save = Some(Instant::now());
+        match self.save() {
+            Ok(()) => self.dirty = false,
+            Err(err) => warn!("Schaltspiele konnten nicht gespeichert werden: {}", err),
+        }
+    }
+
+    /// Schaltspiele des Ausgangs `num` **Eins basiert**
+    pub fn cycles(&self, num: u64) -> u64 {
+        match num {
+            0 => 0,
+            num => self.counts.get((num - 1) as usize).cloned().unwrap_or(0),
+        }
+    }
+
+    /// Ausgänge (Eins basiert) die die `warning_threshold` erreicht haben
+    pub fn worn(&self) -> Vec<u64> {
+        match self.warning_threshold {
+            Some(threshold) => {
+                self.counts
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, count)| *count >= threshold)
+                    .map(|(index, _)| index as u64 + 1)
+                    .collect()
+            },
+            None => vec![],
+        }
+    }
+
+    /// Setzt alle Zähler zurück, z.B. nach der Wartung des Prüfadapters
+    pub fn reset(&mut self) -> Result<()> {
+        self.counts.clear();
+        self.loaded = true;
+        self.dirty = false;
+        try!(self.save());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+//! Diagnose Muster zum Finden von Verdrahtungsfehlern
+//!
+//! `all()` und `test_random()` können einen Kurzschluss zwischen zwei benachbarten Ausgängen
+//! nicht von einer funktionierenden Platine unterscheiden. Die Diagnose Muster schalten die
+//! Ausgänge so, dass sich Kurzschlüsse und klemmende Ausgänge (stuck-at) eingrenzen lassen:
+//!
+//! * `WalkingOne` - immer genau ein Ausgang an
+//! * `WalkingZero` - immer genau ein Ausgang aus
+//! * `Checkerboard`, `InverseCheckerboard` - jeder zweite Ausgang an, benachbarte Ausgänge
+//!   haben also immer unterschiedliche Pegel
+//! * `AddressLines` - für jedes Bit der Ausgangsnummer alle Ausgänge bei denen das Bit gesetzt
+//!   ist, danach invertiert
+//!
+//! Eine `Diagnostic` geht die Schritte eines Musters im Tempo des Bedieners durch (`step()`). Der
+//! Bediener markiert mit `flag()` den aktuellen Schritt, wenn er einen Fehler sieht. Ist eine
+//! Rückmeldung (`readback`) konfiguriert, werden fehlerhafte Schritte zusätzlich automatisch
+//! markiert. Aus den markierten Schritten und den dort geschalteten Ausgängen lässt sich der
+//! Fehler eingrenzen.
+//!
+//! Sind Verriegelungen (`interlocks`) konfiguriert, bleibt in jedem Schritt pro Verriegelung nur
+//! der Ausgang mit der kleinsten Nummer an. Die übrigen werden ausgelassen und von `skipped()`
+//! geliefert, der Schritt prüft diese Ausgänge also nicht.
+//!
+//! # Examples
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! assert_eq!(DiagnosticPattern::Checkerboard.steps(4), vec![vec![0b0101]]);
+//! assert_eq!(DiagnosticPattern::AddressLines.steps(4),
+//!            vec![vec![0b1010], vec![0b0101], vec![0b1100], vec![0b0011]]);
+//!
+//! let readback = Readback { load_pin: 100, clock_pin: 101, data_pin: 102 };
+//! let mock = RecordingBackend::new();
+//! mock.connect_readback(45, 39, 37, &readback);
+//! // Relais 4 klemmt auf high
+//! mock.set_stuck(4, 1);
+//!
+//! let mut relais = ShiftRegister::with_backend(ShiftRegisterType::RELAIS, Box::new(mock.clone()));
+//! relais.readback = Some(readback);
+//!
+//! let mut diagnostic = relais.diagnostic(DiagnosticPattern::WalkingOne);
+//! assert_eq!(diagnostic.len(), 9);
+//! while let Some(step) = diagnostic.step(&mut relais).unwrap() {
+//!     // Der Bediener sieht beim 2. Schritt einen Fehler
+//!     if step == 1 {
+//!         diagnostic.flag();
+//!     }
+//! }
+//!
+//! let flagged = diagnostic.flagged();
+//! let readback: Vec<&FlaggedStep> = flagged.iter().filter(|flag| flag.source == FlagSource::Readback).collect();
+//! // Nur bei Schritt 4 ist Relais 4 tatsächlich an, da fällt der Fehler nicht auf
+//! assert_eq!(readback.iter().map(|flag| flag.step).collect::<Vec<_>>(), vec![0, 1, 2, 4, 5, 6, 7, 8]);
+//! assert!(readback.iter().all(|flag| flag.mismatched == vec![4]));
+//!
+//! let operator: Vec<&FlaggedStep> = flagged.iter().filter(|flag| flag.source == FlagSource::Operator).collect();
+//! assert_eq!(operator[0].step, 1);
+//! assert_eq!(operator[0].active, vec![2]);
+//! ```
+//!
+//! Mit Verriegelung:
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let mock = RecordingBackend::new();
+//! let mut relais = ShiftRegister::with_backend(ShiftRegisterType::RELAIS, Box::new(mock.clone()));
+//! relais.interlocks = vec![Interlock { name: "Alarmstufen".to_string(), outputs: vec![1, 2, 3] }];
+//!
+//! let mut diagnostic = relais.diagnostic(DiagnosticPattern::WalkingZero);
+//! while let Some(_) = diagnostic.step(&mut relais).unwrap() {}
+//!
+//! // Im ersten Schritt ist Ausgang 1 aus, von 2 und 3 darf nur einer an sein
+//! assert_eq!(diagnostic.active(0), vec![2, 4, 5, 6, 7, 8, 9]);
+//! assert_eq!(diagnostic.skipped(0), vec![3]);
+//! // Im letzten Schritt bleibt Ausgang 1 an
+//! assert_eq!(diagnostic.active(8), vec![1, 4, 5, 6, 7, 8]);
+//! assert_eq!(diagnostic.skipped(8), vec![2, 3]);
+//! assert_eq!(mock.latched_data(45, 39, 37).last(), Some(&vec![0b1111_1001, 0]));
+//! ```
+use errors::*;
+use shift_register::interlock::Interlock;
+use shift_register::shift_register::ShiftRegister;
+
+
+/// Art des Diagnose Musters, siehe Modul Dokumentation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum DiagnosticPattern {
+    WalkingOne,
+    WalkingZero,
+    Checkerboard,
+    InverseCheckerboard,
+    AddressLines,
+}
+
+impl DiagnosticPattern {
+    /// Alle Schritte des Musters im Aufbau von `ShiftRegister.data`
+    ///
+    /// # Arguments
+    /// * `outputs`     - Anzahl der vorhandenen Ausgänge
+    pub fn steps(&self, outputs: usize) -> Vec<Vec<u8>> {
+        let indexes: Vec<usize> = (0..outputs).collect();
+        let frame = |filter: &Fn(usize) -> bool| {
+            let mut data = vec![0; (outputs + 7) / 8];
+            for index in indexes.iter().filter(|index| filter(**index)) {
+                data[index / 8] |= 1 << (index % 8);
+            }
+            data
+        };
+
+        match *self {
+            DiagnosticPattern::WalkingOne => {
+                indexes.iter().map(|on| frame(&|index| index == *on)).collect()
+            },
+            DiagnosticPattern::WalkingZero => {
+                indexes.iter().map(|off| frame(&|index| index != *off)).collect()
+            },
+            DiagnosticPattern::Checkerboard => vec![frame(&|index| index % 2 == 0)],
+            DiagnosticPattern::InverseCheckerboard => vec![frame(&|index| index % 2 == 1)],
+            DiagnosticPattern::AddressLines => {
+                // Anzahl der Bits die für die Ausgangsnummern (Null basiert) gebraucht werden
+                let mut bits = 0;
+                while (1 << bits) < outputs {
+                    bits += 1;
+                }
+                (0..bits)
+                    .flat_map(|bit| {
+                        vec![frame(&|index| index & (1 << bit) != 0),
+                             frame(&|index| index & (1 << bit) == 0)]
+                    })
+                    .collect()
+            },
+        }
+    }
+}
+
+/// Wer einen Schritt markiert hat
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlagSource {
+    /// Der Bediener hat einen Fehler gesehen
+    Operator,
+    /// Die Rückmeldung entspricht nicht dem Sollwert
+    Readback,
+}
+
+/// Ein markierter Schritt
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlaggedStep {
+    /// Nummer des Schritts **Null basiert**
+    pub step: usize,
+    pub source: FlagSource,
+    /// Ausgänge die in diesem Schritt an sein sollten
+    pub active: Vec<u64>,
+    /// Ausgänge deren Rückmeldung nicht dem Sollwert entspricht, leer bei `FlagSource::Operator`
+    pub mismatched: Vec<u64>,
+}
+
+/// Schrittweiser Durchlauf eines Diagnose Musters, siehe Modul Dokumentation
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pattern: DiagnosticPattern,
+    steps: Vec<Vec<u8>>,
+    /// Wegen einer Verriegelung ausgelassene Ausgänge, pro Schritt
+    skipped: Vec<Vec<u64>>,
+    /// Zuletzt ausgegebener Schritt
+    current: Option<usize>,
+    flagged: Vec<FlaggedStep>,
+}
+
+impl Diagnostic {
+    /// Erzeugt den Durchlauf für ein Shift Register mit `outputs` Ausgängen
+    ///
+    /// # Arguments
+    /// * `pattern`     - Diagnose Muster
+    /// * `outputs`     - Anzahl der vorhandenen Ausgänge
+    /// * `interlocks`  - Verriegelungen des Shift Registers, siehe Modul Dokumentation
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xmz_mod_touch_test_tool::shift_register::*;
+    ///
+    /// // Ausgang 0 und 5 gibt es nicht, sie werden ignoriert
+    /// let interlocks = vec![Interlock { name: "Alarmstufen".to_string(), outputs: vec![0, 2, 1, 5] }];
+    /// let diagnostic = Diagnostic::new(DiagnosticPattern::WalkingZero, 4, &interlocks);
+    /// assert!(diagnostic.skipped(0).is_empty());
+    /// assert_eq!(diagnostic.skipped(2), vec![2]);
+    /// ```
+    pub fn new(pattern: DiagnosticPattern, outputs: usize, interlocks: &[Interlock]) -> Self {
+        // Ungültige Ausgänge lehnt schon `ShiftRegister::validate()` ab, ohne geprüftes Profil
+        // werden sie hier ignoriert
+        let interlocks: Vec<Interlock> = interlocks.iter()
+            .map(|interlock| Interlock {
+                name: interlock.name.clone(),
+                outputs: interlock.outputs.iter().filter(|num| **num >= 1 && **num <= outputs as u64).cloned().collect(),
+            })
+            .collect();
+        let mut steps = pattern.steps(outputs);
+        let mut skipped = vec![];
+        for data in &mut steps {
+            let mut step_skipped = vec![];
+            for interlock in &interlocks {
+                step_skipped.extend(interlock.keep_lowest(data));
+            }
+            step_skipped.sort();
+            skipped.push(step_skipped);
+        }
+
+        Diagnostic {
+            pattern: pattern,
+            steps: steps,
+            skipped: skipped,
+            current: None,
+            flagged: vec![],
+        }
+    }
+
+    pub fn pattern(&self) -> DiagnosticPattern {
+        self.pattern
+    }
+
+    /// Anzahl der Schritte
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Zuletzt ausgegebener Schritt **Null basiert**, `None` vor dem ersten `step()`
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Alle Schritte im Aufbau von `ShiftRegister.data`, ohne die verriegelten Ausgänge
+    pub fn frames(&self) -> Vec<Vec<u8>> {
+        self.steps.clone()
+    }
+
+    /// Ausgänge die im Schritt `step` an sind
+    pub fn active(&self, step: usize) -> Vec<u64> {
+        let data = match self.steps.get(step) {
+            Some(data) => data,
+            None => return vec![],
+        };
+        (0..data.len() * 8)
+            .filter(|index| data[index / 8] & (1 << (index % 8)) != 0)
+            .map(|index| index as u64 + 1)
+            .collect()
+    }
+
+    /// Ausgänge die im Schritt `step` wegen einer Verriegelung ausgelassen werden
+    pub fn skipped(&self, step: usize) -> Vec<u64> {
+        self.skipped.get(step).cloned().unwrap_or(vec![])
+    }
+
+    /// Gibt den nächsten Schritt aus
+    ///
+    /// Liefert die Nummer des ausgegebenen Schritts, `None` wenn alle Schritte durch sind. Mit
+    /// Rückmeldung wird der Schritt automatisch markiert wenn Ausgänge nicht dem Sollwert
+    /// entsprechen, das ist dann kein Fehler sondern das Ergebnis der Diagnose.
+    pub fn step(&mut self, register: &mut ShiftRegister) -> Result<Option<usize>> {
+        let step = self.current.map(|step| step + 1).unwrap_or(0);
+        if step >= self.steps.len() {
+            return Ok(None);
+        }
+        self.current = Some(step);
+
+        if let Err(err) = register.write(self.steps[step].clone()) {
+            let mismatch = match *err.kind() {
+                ErrorKind::ReadbackMismatch(..) => true,
+                _ => false,
+            };
+            if !mismatch {
+                return Err(err);
+            }
+        }
+        if register.readback.is_some() {
+            let mismatched = try!(register.mismatches());
+            if !mismatched.is_empty() {
+                self.flagged.push(FlaggedStep {
+                    step: step,
+                    source: FlagSource::Readback,
+                    active: self.active(step),
+                    mismatched: mismatched,
+                });
+            }
+        }
+
+        Ok(Some(step))
+    }
+
+    /// Markiert den zuletzt ausgegebenen Schritt als fehlerhaft
+    pub fn flag(&mut self) {
+        if let Some(step) = self.current {
+            let active = self.active(step);
+            self.flagged.push(FlaggedStep {
+                step: step,
+                source: FlagSource::Operator,
+                active: active,
+                mismatched: vec![],
+            });
+        }
+    }
+
+    /// Alle markierten Schritte in der Reihenfolge der Markierung
+    pub fn flagged(&self) -> &[FlaggedStep] {
+        &self.flagged
+    }
+}
@@ -0,0 +1,127 @@
+//! Abstraktion der GPIO Zugriffe
+//!
+//! Das `ShiftRegister` greift nicht direkt auf das sysfs des Linux Kernels zu, sondern über ein
+//! `GpioBackend`. Damit lässt sich die Clock/Latch Logik ohne echte Hardware testen und es
+//! können weitere Treiber angebunden werden, ohne die Bit API des `ShiftRegister` zu ändern.
+//!
+//! # Examples
+//!
+//! Mit dem Character Device Backend kann im Hardware Profil jeder Pin auf einen eigenen Chip und
+//! Offset gelegt werden, die übrigen Pins sind Offsets auf `chip`.
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::profile::HardwareProfile;
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let profile: HardwareProfile = r#"{
+//!     "leds": {
+//!         "register_type": "LED",
+//!         "oe_pin": 0, "ds_pin": 1, "clock_pin": 2, "latch_pin": 3,
+//!         "chips": 3, "outputs": 20,
+//!         "gpio": { "Cdev": { "chip": "/dev/gpiochip0" } }
+//!     },
+//!     "relais": {
+//!         "register_type": "RELAIS",
+//!         "oe_pin": 4, "ds_pin": 5, "clock_pin": 6, "latch_pin": 7,
+//!         "chips": 2, "outputs": 9,
+//!         "gpio": { "Cdev": {
+//!             "chip": "/dev/gpiochip0",
+//!             "lines": [{ "pin": 4, "chip": "/dev/gpiochip2", "offset": 17 }]
+//!         } }
+//!     }
+//! }"#.parse().unwrap();
+//! assert_eq!(profile.relais.gpio, GpioDriver::Cdev {
+//!     chip: "/dev/gpiochip0".to_string(),
+//!     lines: vec![CdevLine { pin: 4, chip: "/dev/gpiochip2".to_string(), offset: 17 }],
+//! });
+//!
+//! // Lines für Pins die das Shift Register nicht verwendet, sind ein Fehler im Profil
+//! let mut relais = ShiftRegister::new(ShiftRegisterType::RELAIS);
+//! relais.gpio = GpioDriver::Cdev {
+//!     chip: "/dev/gpiochip0".to_string(),
+//!     lines: vec![CdevLine { pin: 99, chip: "/dev/gpiochip2".to_string(), offset: 17 }],
+//! };
+//! assert!(relais.validate().is_err());
+//! ```
+use errors::*;
+use shift_register::cdev_backend::{CdevBackend, CdevLine};
+use shift_register::sysfs_backend::SysfsBackend;
+use std::fmt;
+
+
+/// Richtung eines GPIO Pins
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+/// Schnittstelle zu den GPIO Pins eines Shift Registers
+///
+/// Die Pins werden über ihre Nummer angesprochen, wie sie in `ShiftRegister` (`oe_pin`,
+/// `ds_pin`, `clock_pin`, `latch_pin`) hinterlegt sind. Was diese Nummer bedeutet, legt das
+/// jeweilige Backend fest.
+pub trait GpioBackend: fmt::Debug + Send {
+    /// Stellt den Pin zur Verfügung (z.B. Export in das sysfs)
+    fn export(&mut self, pin: u64) -> Result<()>;
+
+    /// Gibt den Pin wieder frei
+    fn unexport(&mut self, pin: u64) -> Result<()>;
+
+    /// Setzt die Richtung des Pins
+    fn set_direction(&mut self, pin: u64, direction: Direction) -> Result<()>;
+
+    /// Setzt den Pin auf `0` (low) oder `1` (high)
+    fn set_value(&mut self, pin: u64, value: u8) -> Result<()>;
+
+    /// Liest den aktuellen Wert des Pins
+    fn get_value(&mut self, pin: u64) -> Result<u8>;
+}
+
+/// Auswahl des GPIO Backends in der Beschreibung eines Shift Registers
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum GpioDriver {
+    /// sysfs Interface (`/sys/class/gpio`), die Pins sind globale Kernel Nummern
+    Sysfs,
+    /// Character Device (`/dev/gpiochipN`), die Pins sind Offsets auf `chip`
+    ///
+    /// Pins die auf einem anderen Chip liegen, werden in `lines` mit Chip und Offset eingetragen.
+    Cdev {
+        chip: String,
+        #[serde(default)]
+        lines: Vec<CdevLine>,
+    },
+    /// Virtuelle 74HC595 Kette, z.B. für `ShiftRegisterType::Simulation`, siehe
+    /// `shift_register::simulated_chain`
+    Simulated,
+    /// Keine Pin Aktivität
+    None,
+}
+
+impl Default for GpioDriver {
+    fn default() -> Self {
+        GpioDriver::Sysfs
+    }
+}
+
+impl GpioDriver {
+    /// Öffnet das ausgewählte Backend, `GpioDriver::None` liefert kein Backend
+    ///
+    /// Die virtuelle Kette von `GpioDriver::Simulated` hängt von den Pins und der Anzahl der
+    /// Shift Register ab, sie wird über `ShiftRegister::simulated_chain()` geöffnet.
+    pub fn open(&self) -> Result<Option<Box<GpioBackend>>> {
+        match *self {
+            GpioDriver::Sysfs => Ok(Some(Box::new(SysfsBackend::new()))),
+            GpioDriver::Cdev { ref chip, ref lines } => {
+                let mut backend = try!(CdevBackend::new(chip));
+                for line in lines {
+                    try!(backend.map_pin(line.pin, &line.chip, line.offset));
+                }
+                Ok(Some(Box::new(backend)))
+            },
+            GpioDriver::Simulated => bail!("Die virtuelle 74HC595 Kette wird über das ShiftRegister geöffnet"),
+            GpioDriver::None => Ok(None),
+        }
+    }
+}
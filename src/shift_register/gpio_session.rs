@@ -0,0 +1,130 @@
+//! Geöffnete GPIO Pins eines Shift Registers
+//!
+//! Die Pins werden beim Öffnen der Session einmal exportiert und als Ausgang konfiguriert. Die
+//! Session bleibt so lange bestehen wie das `ShiftRegister`, ein `shift_out` muss die Pins also
+//! nicht jedes Mal neu exportieren. Beim Schließen bzw. `Drop` werden die Pins wieder freigegeben.
+//!
+//! Der !OE Pin wird beim Öffnen high gezogen, das `ShiftRegister` gibt die Ausgänge erst nach der
+//! ersten kompletten Ausgabe frei.
+//!
+//! # Examples
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//! use xmz_mod_touch_test_tool::shift_register::mock_backend::PinAction;
+//!
+//! let mock = RecordingBackend::new();
+//! {
+//!     let mut leds = ShiftRegister::with_backend(ShiftRegisterType::LED, Box::new(mock.clone()));
+//!     leds.set(1).unwrap();
+//!     leds.set(2).unwrap();
+//!     leds.set(3).unwrap();
+//! }
+//! let events = mock.events();
+//! // !OE (276) geht erst nach dem ersten Latch (40) low
+//! let seq = |pin, value| events.iter()
+//!     .find(|event| event.pin == pin && event.action == PinAction::Value(value))
+//!     .map(|event| event.seq);
+//! assert_eq!(mock.values(276).first(), Some(&1));
+//! assert!(seq(276, 0) > seq(40, 1));
+//! let exports = events.iter().filter(|event| event.action == PinAction::Export).count();
+//! let unexports = events.iter().filter(|event| event.action == PinAction::Unexport).count();
+//! // OE, DS, CLOCK und LATCH werden genau einmal exportiert und beim Drop wieder freigegeben
+//! assert_eq!(exports, 4);
+//! assert_eq!(unexports, 4);
+//! ```
+use errors::*;
+use shift_register::gpio_backend::{Direction, GpioBackend};
+
+
+/// Exportierte und als Ein- bzw. Ausgang konfigurierte Pins, siehe Modul Dokumentation
+#[derive(Debug)]
+pub struct GpioSession {
+    /// Ist nur nach `close()` leer
+    backend: Option<Box<GpioBackend>>,
+    pins: Vec<u64>,
+}
+
+impl GpioSession {
+    /// Exportiert die Pins, schaltet sie als Ausgang und zieht sie low
+    ///
+    /// # Arguments
+    /// * `backend`     - GPIO Backend über das die Pins geschaltet werden
+    /// * `pins`        - Pins die zur Session gehören
+    pub fn open(backend: Box<GpioBackend>, pins: Vec<u64>) -> Result<Self> {
+        GpioSession::open_with_inputs(backend, pins, vec![], None)
+    }
+
+    /// Wie `open()`, zusätzlich werden die `inputs` als Eingang exportiert
+    ///
+    /// Der `oe_pin` wird als einziger Ausgang high gezogen, die Ausgänge der Shift Register bleiben
+    /// damit aus bis ein definierter Stand ausgegeben wurde. Nach dem Einschalten liegt in den
+    /// Shift Registern ein zufälliger Stand.
+    ///
+    /// # Arguments
+    /// * `backend`     - GPIO Backend über das die Pins geschaltet werden
+    /// * `outputs`     - Pins die als Ausgang konfiguriert und low gezogen werden
+    /// * `inputs`      - Pins die als Eingang konfiguriert werden
+    /// * `oe_pin`      - !OE Pin unter den `outputs`, wird high gezogen
+    pub fn open_with_inputs(mut backend: Box<GpioBackend>, outputs: Vec<u64>, inputs: Vec<u64>, oe_pin: Option<u64>) -> Result<Self> {
+        for pin in &outputs {
+            try!(backend.export(*pin));
+            try!(backend.set_direction(*pin, Direction::Out));
+            let value = match oe_pin {
+                Some(oe_pin) if oe_pin == *pin => 1,
+                _ => 0,
+            };
+            try!(backend.set_value(*pin, value));
+        }
+        for pin in &inputs {
+            try!(backend.export(*pin));
+            try!(backend.set_direction(*pin, Direction::In));
+        }
+
+        Ok(GpioSession {
+            backend: Some(backend),
+            pins: outputs.into_iter().chain(inputs).collect(),
+        })
+    }
+
+    /// Setzt den Pin auf `0` (low) oder `1` (high)
+    pub fn set_value(&mut self, pin: u64, value: u8) -> Result<()> {
+        if let Some(ref mut backend) = self.backend {
+            try!(backend.set_value(pin, value));
+        }
+
+        Ok(())
+    }
+
+    /// Liest den aktuellen Wert des Pins
+    pub fn get_value(&mut self, pin: u64) -> Result<u8> {
+        match self.backend {
+            Some(ref mut backend) => backend.get_value(pin),
+            None => Ok(0),
+        }
+    }
+
+    /// Gibt die Pins frei und liefert das Backend zurück, damit es erneut geöffnet werden kann
+    pub fn close(mut self) -> Result<Box<GpioBackend>> {
+        try!(self.unexport());
+
+        Ok(self.backend.take().expect("Backend ist bis zum close() vorhanden"))
+    }
+
+    fn unexport(&mut self) -> Result<()> {
+        if let Some(ref mut backend) = self.backend {
+            for pin in &self.pins {
+                try!(backend.unexport(*pin));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for GpioSession {
+    fn drop(&mut self) {
+        // Fehler beim Freigeben können hier nur noch ignoriert werden
+        let _ = self.unexport();
+    }
+}
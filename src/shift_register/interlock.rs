@@ -0,0 +1,82 @@
+//! Verriegelungen zwischen Ausgängen
+//!
+//! Manche Relais der xMZ-Mod-Touch dürfen nie gleichzeitig angezogen sein, z.B. die beiden
+//! Alarmstufen wenn mit echten Lasten getestet wird. Eine Verriegelung fasst solche Ausgänge zu
+//! einer Gruppe zusammen, von der höchstens ein Ausgang gleichzeitig an sein darf. Ein verbotenes
+//! Paar ist einfach eine Gruppe mit zwei Ausgängen.
+//!
+//! Die Verriegelungen werden im Hardware Profil unter `interlocks` hinterlegt und vom
+//! `ShiftRegister` vor jeder Ausgabe geprüft. Ein Zustand der eine Verriegelung verletzt wird mit
+//! `ErrorKind::InterlockViolation` abgelehnt, der bisherige Zustand bleibt dabei erhalten. Nur
+//! `ShiftRegister::all()` lässt die höheren Ausgänge jeder Verriegelung einfach aus.
+//!
+//! # Examples
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::errors::*;
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let mut relais = ShiftRegister::new(ShiftRegisterType::RELAIS);
+//! relais.gpio = GpioDriver::None;
+//! relais.interlocks = vec![Interlock { name: "Alarmstufen".to_string(), outputs: vec![1, 2] }];
+//!
+//! relais.set(1).unwrap();
+//! let err = relais.set(2).unwrap_err();
+//! match *err.kind() {
+//!     ErrorKind::InterlockViolation(_, ref name, _) => assert_eq!(name, "Alarmstufen"),
+//!     _ => panic!("Relais 2 hätte abgelehnt werden müssen"),
+//! }
+//! assert_eq!(relais.get(2).unwrap(), false);
+//!
+//! // Bei alle an bleibt nur der niedrigste Ausgang an, der Zufallstest hält sich ebenfalls daran
+//! assert_eq!(relais.all().unwrap(), vec![2]);
+//! assert_eq!((relais.get(1).unwrap(), relais.get(2).unwrap()), (true, false));
+//! relais.test_random(None).unwrap();
+//! assert!(!(relais.get(1).unwrap() && relais.get(2).unwrap()));
+//! ```
+
+
+/// Gruppe von Ausgängen von denen höchstens einer gleichzeitig an sein darf
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct Interlock {
+    /// Bezeichnung der Verriegelung, erscheint in der Fehlermeldung
+    pub name: String,
+    /// Ausgänge der Gruppe **Eins basiert**
+    pub outputs: Vec<u64>,
+}
+
+impl Interlock {
+    /// Alle Ausgänge der Gruppe die in `data` an sind
+    ///
+    /// # Arguments
+    /// * `data`    - Zustand im Aufbau von `ShiftRegister.data`
+    pub fn active(&self, data: &[u8]) -> Vec<u64> {
+        self.outputs
+            .iter()
+            .filter(|num| {
+                let index = (**num as usize).saturating_sub(1);
+                data.get(index / 8).map(|byte| byte & (1 << (index % 8)) != 0).unwrap_or(false)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Schaltet in `data` alle Ausgänge der Gruppe bis auf den niedrigsten aktiven aus
+    ///
+    /// Geliefert werden die ausgeschalteten Ausgänge, aufsteigend sortiert.
+    ///
+    /// # Arguments
+    /// * `data`    - Zustand im Aufbau von `ShiftRegister.data`
+    pub fn keep_lowest(&self, data: &mut [u8]) -> Vec<u64> {
+        let mut active = self.active(data);
+        active.sort();
+        let skipped: Vec<u64> = active.into_iter().skip(1).collect();
+        for num in &skipped {
+            let index = (num - 1) as usize;
+            data[index / 8] &= !(1 << (index % 8));
+        }
+
+        skipped
+    }
+}
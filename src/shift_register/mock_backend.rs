@@ -0,0 +1,332 @@
+//! GPIO Backend das alle Pin Zugriffe aufzeichnet
+//!
+//! Das `RecordingBackend` schaltet keine Hardware, sondern merkt sich jeden Pin Wechsel mit einer
+//! fortlaufenden Nummer. Aus der Aufzeichnung der DS/CLOCK/LATCH Pins lassen sich die Daten
+//! zurückgewinnen, die eine 74HC595 Kette beim Latch übernommen hätte.
+//!
+//! Das Backend ist `Clone`, alle Klone teilen sich die selbe Aufzeichnung. So kann ein Klon dem
+//! `ShiftRegister` übergeben und der andere danach ausgewertet werden.
+//!
+//! Mit `connect_readback()` wird zusätzlich eine 74HC165 Kette nachgebildet, die die gelatchten
+//! Ausgänge zurückliefert, siehe `shift_register::readback`.
+//!
+//! # Examples
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let mock = RecordingBackend::new();
+//! let mut leds = ShiftRegister::with_backend(ShiftRegisterType::LED, Box::new(mock.clone()));
+//! let (oe, ds, clock, latch) = (276, 38, 44, 40);
+//!
+//! leds.set(1).unwrap();
+//! leds.set(3).unwrap();
+//! leds.clear(1).unwrap();
+//! leds.toggle(2).unwrap();
+//! assert_eq!(mock.latched_data(ds, clock, latch),
+//!            vec![vec![0b1, 0, 0], vec![0b101, 0, 0], vec![0b100, 0, 0], vec![0b110, 0, 0]]);
+//!
+//! mock.clear();
+//! leds.all().unwrap();
+//! leds.reset().unwrap();
+//! // 20 LED auf 3 Shift Register
+//! assert_eq!(mock.latched_data(ds, clock, latch), vec![vec![0xff, 0xff, 0x0f], vec![0, 0, 0]]);
+//!
+//! // Jedes Shift Register wird mit 8 Clock Pulsen eingetaktet und erst danach gelatched
+//! for pulse in mock.decode(ds, clock, latch) {
+//!     assert_eq!(pulse.clocks, 3 * 8);
+//! }
+//! // !OE bleibt die ganze Zeit low, die Ausgänge sind also immer aktiv
+//! assert!(mock.values(oe).iter().all(|value| *value == 0));
+//! ```
+//!
+//! Die Testmuster des `ShiftRegister` lassen sich genauso prüfen.
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let mock = RecordingBackend::new();
+//! let mut relais = ShiftRegister::with_backend(ShiftRegisterType::RELAIS, Box::new(mock.clone()));
+//! let (ds, clock, latch) = (45, 39, 37);
+//!
+//! relais.set(2).unwrap();
+//! mock.clear();
+//! relais.test().unwrap();
+//! // Alle an, alle aus, alter Stand
+//! assert_eq!(mock.latched_data(ds, clock, latch), vec![vec![0xff, 0b1], vec![0, 0], vec![0b10, 0]]);
+//!
+//! mock.clear();
+//! relais.test_random(None).unwrap();
+//! assert_eq!(mock.latched_data(ds, clock, latch), vec![relais.data.clone()]);
+//! ```
+use errors::*;
+use shift_register::gpio_backend::{Direction, GpioBackend};
+use shift_register::readback::Readback;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+
+/// Art des aufgezeichneten Pin Zugriffs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinAction {
+    Export,
+    Unexport,
+    Direction(Direction),
+    Value(u8),
+}
+
+/// Ein aufgezeichneter Pin Zugriff
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PinEvent {
+    /// Fortlaufende Nummer über alle Pins
+    pub seq: usize,
+    pub pin: u64,
+    pub action: PinAction,
+}
+
+/// Ein Latch Puls, so wie ihn die 74HC595 Kette gesehen hätte
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Latch {
+    /// Fortlaufende Nummer der steigenden Flanke am Latch Pin
+    pub seq: usize,
+    /// Seit dem letzten Latch eingetaktete Daten, im Aufbau von `ShiftRegister.data`
+    ///
+    /// Das zuletzt eingetaktete Bit ist Bit 0 von `data[0]`.
+    pub data: Vec<u8>,
+    /// Anzahl Clock Pulse seit dem letzten Latch
+    pub clocks: usize,
+}
+
+/// Nachgebildete 74HC165 Kette an den Ausgängen der 74HC595 Kette
+#[derive(Debug)]
+struct ReadbackLoop {
+    ds_pin: u64,
+    clock_pin: u64,
+    latch_pin: u64,
+    readback: Readback,
+    /// Ausgänge (Eins basiert) die unabhängig vom Sollwert auf einem Pegel klemmen
+    stuck: HashMap<u64, u8>,
+}
+
+#[derive(Debug, Default)]
+struct Recording {
+    events: Vec<PinEvent>,
+    values: HashMap<u64, u8>,
+    readback: Option<ReadbackLoop>,
+}
+
+/// Zeichnet alle Pin Zugriffe auf, siehe Modul Dokumentation
+#[derive(Clone, Debug, Default)]
+pub struct RecordingBackend {
+    recording: Arc<Mutex<Recording>>,
+}
+
+impl RecordingBackend {
+    pub fn new() -> Self {
+        RecordingBackend::default()
+    }
+
+    fn record(&self, pin: u64, action: PinAction) {
+        let mut recording = self.recording.lock().unwrap();
+        let seq = recording.events.len();
+        recording.events.push(PinEvent {
+            seq: seq,
+            pin: pin,
+            action: action,
+        });
+        if let PinAction::Value(value) = action {
+            recording.values.insert(pin, value);
+        }
+    }
+
+    /// Alle bisher aufgezeichneten Pin Zugriffe
+    pub fn events(&self) -> Vec<PinEvent> {
+        self.recording.lock().unwrap().events.clone()
+    }
+
+    /// Verwirft die Aufzeichnung, die aktuellen Pin Pegel bleiben erhalten
+    pub fn clear(&self) {
+        self.recording.lock().unwrap().events.clear();
+    }
+
+    /// Aktueller Pegel des Pins, `None` wenn der Pin noch nie geschaltet wurde
+    pub fn value(&self, pin: u64) -> Option<u8> {
+        self.recording.lock().unwrap().values.get(&pin).cloned()
+    }
+
+    /// Alle Pegel die auf den Pin geschrieben wurden, in zeitlicher Reihenfolge
+    pub fn values(&self, pin: u64) -> Vec<u8> {
+        self.events()
+            .iter()
+            .filter(|event| event.pin == pin)
+            .filter_map(|event| match event.action {
+                PinAction::Value(value) => Some(value),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Dekodiert die Aufzeichnung der DS/CLOCK/LATCH Pins
+    ///
+    /// Bei jeder steigenden Flanke am Clock Pin wird der Pegel des DS Pins übernommen, bei jeder
+    /// steigenden Flanke am Latch Pin werden die seit dem letzten Latch eingetakteten Bits
+    /// ausgegeben.
+    ///
+    /// # Arguments
+    /// * `ds_pin`      - Daten Pin
+    /// * `clock_pin`   - Clock Pin
+    /// * `latch_pin`   - Latch Pin
+    pub fn decode(&self, ds_pin: u64, clock_pin: u64, latch_pin: u64) -> Vec<Latch> {
+        let mut latches = vec![];
+        let mut levels: HashMap<u64, u8> = HashMap::new();
+        let mut bits: Vec<u8> = vec![];
+
+        for event in self.events() {
+            let value = match event.action {
+                PinAction::Value(value) => value,
+                _ => continue,
+            };
+            let rising = value == 1 && levels.get(&event.pin).cloned().unwrap_or(0) == 0;
+            levels.insert(event.pin, value);
+
+            if rising && event.pin == clock_pin {
+                bits.push(levels.get(&ds_pin).cloned().unwrap_or(0));
+            } else if rising && event.pin == latch_pin {
+                let mut data = vec![0; (bits.len() + 7) / 8];
+                for (i, bit) in bits.iter().rev().enumerate() {
+                    data[i / 8] |= bit << (i % 8);
+                }
+                latches.push(Latch {
+                    seq: event.seq,
+                    data: data,
+                    clocks: bits.len(),
+                });
+                bits.clear();
+            }
+        }
+
+        latches
+    }
+
+    /// Verbindet die Ausgänge der 74HC595 Kette mit einer nachgebildeten 74HC165 Kette
+    ///
+    /// `get_value()` auf dem `data_pin` liefert danach die Bits der zuletzt gelatchten Daten,
+    /// so wie sie über `load_pin` und `clock_pin` aus der 74HC165 Kette geschoben werden.
+    ///
+    /// # Arguments
+    /// * `ds_pin`      - Daten Pin der 74HC595 Kette
+    /// * `clock_pin`   - Clock Pin der 74HC595 Kette
+    /// * `latch_pin`   - Latch Pin der 74HC595 Kette
+    /// * `readback`    - Pins der 74HC165 Kette
+    pub fn connect_readback(&self, ds_pin: u64, clock_pin: u64, latch_pin: u64, readback: &Readback) {
+        self.recording.lock().unwrap().readback = Some(ReadbackLoop {
+            ds_pin: ds_pin,
+            clock_pin: clock_pin,
+            latch_pin: latch_pin,
+            readback: readback.clone(),
+            stuck: HashMap::new(),
+        });
+    }
+
+    /// Lässt den Ausgang `num` (Eins basiert) in der 74HC165 Kette auf `value` klemmen
+    pub fn set_stuck(&self, num: u64, value: u8) {
+        if let Some(ref mut readback) = self.recording.lock().unwrap().readback {
+            readback.stuck.insert(num, value);
+        }
+    }
+
+    /// Bit das die nachgebildete 74HC165 Kette gerade an `QH` ausgibt
+    fn readback_value(&self) -> u8 {
+        let (ds_pin, clock_pin, latch_pin, readback, stuck) = match self.recording.lock().unwrap().readback {
+            Some(ref fixture) => (fixture.ds_pin, fixture.clock_pin, fixture.latch_pin,
+                                  fixture.readback.clone(), fixture.stuck.clone()),
+            None => return 0,
+        };
+
+        // Letzte steigende Flanke am Load Pin und Anzahl der Clock Pulse seitdem
+        let mut levels: HashMap<u64, u8> = HashMap::new();
+        let mut load_seq = None;
+        let mut clocks = 0;
+        for event in self.events() {
+            let value = match event.action {
+                PinAction::Value(value) => value,
+                _ => continue,
+            };
+            let rising = value == 1 && levels.get(&event.pin).cloned().unwrap_or(0) == 0;
+            levels.insert(event.pin, value);
+
+            if rising && event.pin == readback.load_pin {
+                load_seq = Some(event.seq);
+                clocks = 0;
+            } else if rising && event.pin == readback.clock_pin {
+                clocks += 1;
+            }
+        }
+        let load_seq = match load_seq {
+            Some(seq) => seq,
+            None => return 0,
+        };
+
+        // Beim Laden übernommene Ausgänge, SER der Kette liegt auf low
+        let data = self.decode(ds_pin, clock_pin, latch_pin)
+            .into_iter()
+            .filter(|latch| latch.seq < load_seq)
+            .last()
+            .map(|latch| latch.data)
+            .unwrap_or(vec![]);
+        if clocks >= data.len() * 8 {
+            return 0;
+        }
+        let index = data.len() * 8 - 1 - clocks;
+        match stuck.get(&(index as u64 + 1)) {
+            Some(value) => *value,
+            None => (data[index / 8] >> (index % 8)) & 1,
+        }
+    }
+
+    /// Wie `decode()`, liefert aber nur die gelatchten Daten
+    pub fn latched_data(&self, ds_pin: u64, clock_pin: u64, latch_pin: u64) -> Vec<Vec<u8>> {
+        self.decode(ds_pin, clock_pin, latch_pin)
+            .into_iter()
+            .map(|latch| latch.data)
+            .collect()
+    }
+}
+
+impl GpioBackend for RecordingBackend {
+    fn export(&mut self, pin: u64) -> Result<()> {
+        self.record(pin, PinAction::Export);
+
+        Ok(())
+    }
+
+    fn unexport(&mut self, pin: u64) -> Result<()> {
+        self.record(pin, PinAction::Unexport);
+
+        Ok(())
+    }
+
+    fn set_direction(&mut self, pin: u64, direction: Direction) -> Result<()> {
+        self.record(pin, PinAction::Direction(direction));
+
+        Ok(())
+    }
+
+    fn set_value(&mut self, pin: u64, value: u8) -> Result<()> {
+        self.record(pin, PinAction::Value(value));
+
+        Ok(())
+    }
+
+    fn get_value(&mut self, pin: u64) -> Result<u8> {
+        let readback_pin = match self.recording.lock().unwrap().readback {
+            Some(ref fixture) => Some(fixture.readback.data_pin),
+            None => None,
+        };
+        if readback_pin == Some(pin) {
+            return Ok(self.readback_value());
+        }
+
+        Ok(self.value(pin).unwrap_or(0))
+    }
+}
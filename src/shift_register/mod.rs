@@ -2,6 +2,38 @@
 //!
 //! Die Relais und LED sind über 8bit serielle Shift Register angeschlossen. Dieser Teil der
 //! Software dient zur Verwaltung und Kontrolle dieser.
+pub mod cdev_backend;
+pub mod colour;
+pub mod cycle_counter;
+pub mod diagnostic;
+pub mod gpio_backend;
+pub mod gpio_session;
+pub mod interlock;
+pub mod mock_backend;
+pub mod pattern;
+pub mod pwm;
+pub mod random_test;
+pub mod readback;
+pub mod safe_state;
 pub mod shift_register;
+pub mod simulated_chain;
+pub mod spi_driver;
+pub mod sysfs_backend;
 
-pub use self::shift_register::{ShiftRegister, ShiftRegisterType};
+pub use self::cdev_backend::{CdevBackend, CdevLine};
+pub use self::colour::Colour;
+pub use self::cycle_counter::CycleCounter;
+pub use self::diagnostic::{Diagnostic, DiagnosticPattern, FlaggedStep, FlagSource};
+pub use self::gpio_backend::{Direction, GpioBackend, GpioDriver};
+pub use self::gpio_session::GpioSession;
+pub use self::interlock::Interlock;
+pub use self::mock_backend::RecordingBackend;
+pub use self::pattern::{Pattern, PatternRunner};
+pub use self::pwm::{PwmConfig, SoftPwm};
+pub use self::random_test::RandomTest;
+pub use self::readback::Readback;
+pub use self::safe_state::OutputEnable;
+pub use self::shift_register::{ShiftRegister, ShiftRegisterType, Transaction};
+pub use self::simulated_chain::SimulatedChain;
+pub use self::spi_driver::{LoopbackBus, OutputDriver, SpiBus, SpidevBus};
+pub use self::sysfs_backend::SysfsBackend;
@@ -0,0 +1,198 @@
+//! Zeitgesteuerte Muster für LED und Relais
+//!
+//! Ein `Pattern` beschreibt eine Folge von Zuständen (Frames) der Ausgänge. Der `PatternRunner`
+//! spielt diese Frames in einem eigenen Thread ab. Der Mutex des `ShiftRegister` wird dabei nur
+//! für die Ausgabe eines einzelnen Frames gehalten, so bleibt die GUI während des Musters
+//! bedienbar. Mit `stop()` bzw. beim `Drop` wird das Muster abgebrochen.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//! use std::time::Duration;
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! assert_eq!(Pattern::Chase.frames(3), vec![vec![0b001], vec![0b010], vec![0b100]]);
+//! assert_eq!(Pattern::KnightRider.frames(3), vec![vec![0b001], vec![0b010], vec![0b100], vec![0b010]]);
+//! assert_eq!(Pattern::Fill.frames(3), vec![vec![0b001], vec![0b011], vec![0b111]]);
+//! assert_eq!(Pattern::Blink.frames(3), vec![vec![0b111], vec![0]]);
+//!
+//! let leds = Arc::new(Mutex::new(ShiftRegister::new(ShiftRegisterType::Simulation)));
+//!
+//! // Einmal durchlaufen
+//! let runner = PatternRunner::start(leds.clone(), Pattern::Chase, Duration::from_millis(1), false);
+//! while !runner.is_finished() {
+//!     ::std::thread::sleep(Duration::from_millis(1));
+//! }
+//! runner.stop().unwrap();
+//! assert_eq!(leds.lock().unwrap().data, vec![0, 0, 0, 0, 0, 0, 0, 0b1000_0000]);
+//!
+//! // Endlos wiederholen, bis zum Abbruch
+//! let runner = PatternRunner::start(leds.clone(), Pattern::Blink, Duration::from_millis(5), true);
+//! ::std::thread::sleep(Duration::from_millis(20));
+//! assert!(!runner.is_finished());
+//! // Der Mutex ist zwischen den Frames frei
+//! leds.lock().unwrap();
+//! runner.stop().unwrap();
+//! ```
+use errors::*;
+use shift_register::diagnostic::DiagnosticPattern;
+use shift_register::shift_register::ShiftRegister;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+
+/// Abfrage Intervall für den Abbruch während der Wartezeit zwischen zwei Frames
+const CANCEL_POLL_MS: u64 = 10;
+
+/// Folge von Zuständen der Ausgänge
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum Pattern {
+    /// Immer nur ein Ausgang an, der Reihe nach
+    Chase,
+    /// Wie `Chase`, aber vor und zurück
+    KnightRider,
+    /// Alle Ausgänge an, alle aus
+    Blink,
+    /// Ein Ausgang nach dem anderen wird dazu geschaltet
+    Fill,
+    /// Eigene Frames im Aufbau von `ShiftRegister.data`
+    Frames(Vec<Vec<u8>>),
+    /// Schritte eines Diagnose Musters, der `PatternRunner` lässt dabei verriegelte Ausgänge aus
+    Diagnostic(DiagnosticPattern),
+}
+
+impl Pattern {
+    /// Alle Frames des Musters im Aufbau von `ShiftRegister.data`
+    ///
+    /// # Arguments
+    /// * `outputs`     - Anzahl der vorhandenen Ausgänge
+    pub fn frames(&self, outputs: usize) -> Vec<Vec<u8>> {
+        let nums: Vec<usize> = (0..outputs).collect();
+        match *self {
+            Pattern::Chase => nums.iter().map(|num| frame(outputs, &[*num])).collect(),
+            Pattern::KnightRider => {
+                let back = nums.iter().rev().skip(1).take(outputs.saturating_sub(2));
+                nums.iter().chain(back).map(|num| frame(outputs, &[*num])).collect()
+            },
+            Pattern::Blink => vec![frame(outputs, &nums), frame(outputs, &[])],
+            Pattern::Fill => (1..outputs + 1).map(|count| frame(outputs, &nums[..count])).collect(),
+            Pattern::Frames(ref frames) => frames.clone(),
+            Pattern::Diagnostic(pattern) => pattern.steps(outputs),
+        }
+    }
+}
+
+/// Frame in dem die Ausgänge `indexes` (Null basiert) an sind
+fn frame(outputs: usize, indexes: &[usize]) -> Vec<u8> {
+    let mut data = vec![0; (outputs + 7) / 8];
+    for index in indexes {
+        data[index / 8] |= 1 << (index % 8);
+    }
+
+    data
+}
+
+/// Spielt ein `Pattern` in einem eigenen Thread ab, siehe Modul Dokumentation
+#[derive(Debug)]
+pub struct PatternRunner {
+    cancel: Arc<AtomicBool>,
+    finished: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl PatternRunner {
+    /// Startet das Muster
+    ///
+    /// # Arguments
+    /// * `register`    - Shift Register auf dem das Muster ausgegeben wird
+    /// * `pattern`     - Abzuspielendes Muster
+    /// * `interval`    - Anzeigedauer jedes Frames
+    /// * `repeat`      - `true` wiederholt das Muster bis zum Abbruch
+    pub fn start(register: Arc<Mutex<ShiftRegister>>, pattern: Pattern, interval: Duration, repeat: bool) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let finished = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let cancel = cancel.clone();
+            let finished = finished.clone();
+            thread::spawn(move || {
+                let result = run(&register, &pattern, interval, repeat, &cancel);
+                finished.store(true, Ordering::SeqCst);
+                result
+            })
+        };
+
+        PatternRunner {
+            cancel: cancel,
+            finished: finished,
+            thread: Some(thread),
+        }
+    }
+
+    /// `true` wenn das Muster durchgelaufen, abgebrochen oder mit einem Fehler beendet ist
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::SeqCst)
+    }
+
+    /// Bricht das Muster ab und wartet auf das Ende des Threads
+    ///
+    /// Liefert den Fehler, mit dem das Muster ggf. abgebrochen ist.
+    pub fn stop(mut self) -> Result<()> {
+        self.cancel.store(true, Ordering::SeqCst);
+        match self.thread.take().map(|thread| thread.join()) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => bail!("Thread des Musters ist abgestürzt"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for PatternRunner {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(register: &Arc<Mutex<ShiftRegister>>, pattern: &Pattern, interval: Duration, repeat: bool,
+       cancel: &AtomicBool) -> Result<()> {
+    let frames = {
+        let register = register.lock().unwrap();
+        match *pattern {
+            Pattern::Diagnostic(pattern) => register.diagnostic(pattern).frames(),
+            ref pattern => pattern.frames(register.outputs),
+        }
+    };
+    loop {
+        for frame in &frames {
+            if cancel.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            // Der Lock wird nur für die Ausgabe des Frames gehalten
+            try!(register.lock().unwrap().write(frame.clone()));
+            sleep(interval, cancel);
+        }
+        if !repeat || frames.is_empty() {
+            return Ok(());
+        }
+    }
+}
+
+/// Wartet `duration`, kehrt bei einem Abbruch aber vorzeitig zurück
+fn sleep(duration: Duration, cancel: &AtomicBool) {
+    let start = Instant::now();
+    while !cancel.load(Ordering::SeqCst) {
+        let elapsed = start.elapsed();
+        if elapsed >= duration {
+            return;
+        }
+        let remaining = duration - elapsed;
+        thread::sleep(::std::cmp::min(remaining, Duration::from_millis(CANCEL_POLL_MS)));
+    }
+}
@@ -0,0 +1,131 @@
+//! Dimmen der Ausgänge über den !OE Pin
+//!
+//! Der !OE Pin der 74HC595 schaltet alle Ausgänge gemeinsam ab (high) bzw. frei (low). Wird er
+//! per PWM angesteuert, lassen sich z.B. die LED dimmen. Ist der !OE Pin mit einem PWM Ausgang des
+//! SoC verbunden, wird das sysfs PWM Interface des Kernels verwendet (`PwmConfig`). Ansonsten
+//! schaltet ein Thread den !OE GPIO Pin im Takt (`SoftPwm`).
+use errors::*;
+use shift_register::gpio_session::GpioSession;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+
+/// Periodendauer der Software PWM, 100Hz
+const SOFT_PWM_PERIOD_NS: u32 = 10_000_000;
+
+/// PWM Kanal im sysfs (`/sys/class/pwm/pwmchipN/pwmM`) an dem der !OE Pin hängt
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct PwmConfig {
+    pub chip: u32,
+    pub channel: u32,
+    pub period_ns: u32,
+}
+
+impl PwmConfig {
+    fn chip_path(&self) -> PathBuf {
+        PathBuf::from(format!("/sys/class/pwm/pwmchip{}", self.chip))
+    }
+
+    fn channel_path(&self) -> PathBuf {
+        self.chip_path().join(format!("pwm{}", self.channel))
+    }
+
+    /// Stellt ein wie viel Prozent der Periode der !OE Pin high ist
+    ///
+    /// # Arguments
+    /// * `percent_high`    - `0` Ausgänge dauerhaft an, `100` Ausgänge dauerhaft aus
+    pub fn set_high_ratio(&self, percent_high: u8) -> Result<()> {
+        if !self.channel_path().exists() {
+            try!(write_sysfs(&self.chip_path().join("export"), self.channel));
+        }
+        let duty_cycle = self.period_ns as u64 * percent_high.min(100) as u64 / 100;
+        try!(write_sysfs(&self.channel_path().join("period"), self.period_ns));
+        try!(write_sysfs(&self.channel_path().join("duty_cycle"), duty_cycle));
+        try!(write_sysfs(&self.channel_path().join("enable"), 1));
+
+        Ok(())
+    }
+}
+
+fn write_sysfs<T: ToString>(path: &Path, value: T) -> Result<()> {
+    let mut file = try!(File::create(path));
+    try!(file.write_all(value.to_string().as_bytes()));
+
+    Ok(())
+}
+
+/// Software PWM auf einem GPIO Pin der Session
+///
+/// Der Thread läuft bis die `SoftPwm` verworfen wird.
+#[derive(Debug)]
+pub struct SoftPwm {
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SoftPwm {
+    /// Startet den PWM Thread
+    ///
+    /// # Arguments
+    /// * `session`         - Session über die der Pin geschaltet wird
+    /// * `pin`             - Pin der getaktet wird, normalerweise !OE
+    /// * `percent_high`    - Anteil der Periode in dem der Pin high ist
+    pub fn start(session: Arc<Mutex<GpioSession>>, pin: u64, percent_high: u8) -> Self {
+        let running = Arc::new(AtomicBool::new(true));
+        let high = Duration::new(0, SOFT_PWM_PERIOD_NS / 100 * percent_high.min(100) as u32);
+        let low = Duration::new(0, SOFT_PWM_PERIOD_NS) - high;
+
+        let thread = {
+            let running = running.clone();
+            thread::spawn(move || {
+                while running.load(Ordering::SeqCst) {
+                    if session.lock().unwrap().set_value(pin, 1).is_err() {
+                        break;
+                    }
+                    thread::sleep(high);
+                    // Angehalten während der Pin high war, z.B. vom sicheren Zustand
+                    if !running.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if session.lock().unwrap().set_value(pin, 0).is_err() {
+                        break;
+                    }
+                    thread::sleep(low);
+                }
+            })
+        };
+
+        SoftPwm {
+            running: running,
+            thread: Some(thread),
+        }
+    }
+
+    /// Flag über das der Thread auch ohne Zugriff auf die `SoftPwm` angehalten werden kann
+    pub fn running(&self) -> Arc<AtomicBool> {
+        self.running.clone()
+    }
+
+    /// Hält den Thread über sein `running` Flag an
+    ///
+    /// Wartet eine Periode, danach schaltet der Thread den Pin garantiert nicht mehr.
+    pub fn halt(running: &AtomicBool) {
+        running.store(false, Ordering::SeqCst);
+        thread::sleep(Duration::new(0, SOFT_PWM_PERIOD_NS));
+    }
+}
+
+impl Drop for SoftPwm {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
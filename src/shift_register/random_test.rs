@@ -0,0 +1,96 @@
+//! Reproduzierbare Zufallstests
+//!
+//! Ein Fehler der beim Zufallstest in der Fertigung auftritt, muss sich am Arbeitsplatz
+//! nachstellen lassen. Die Zufallsdaten werden deshalb aus einem Seed erzeugt. Der Seed wird
+//! entweder vorgegeben oder neu erzeugt und zusammen mit den ausgegebenen Frames als `RandomTest`
+//! geliefert und ins Log geschrieben. Mit dem selben Seed und der selben Anzahl Frames entstehen
+//! auf jedem Rechner wieder die selben Frames.
+//!
+//! Die Frames enthalten nur tatsächlich vorhandene Ausgänge und halten die Verriegelungen ein.
+//!
+//! # Examples
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let mut relais = ShiftRegister::new(ShiftRegisterType::RELAIS);
+//! relais.gpio = GpioDriver::None;
+//!
+//! let test = relais.test_random(None).unwrap();
+//! assert_eq!(test.frames.len(), 1);
+//! assert_eq!(relais.data, test.frames[0]);
+//! // Nur die 9 vorhandenen Relais
+//! assert_eq!(relais.data[1] & !0b1, 0);
+//!
+//! // Mit dem Seed aus dem Log wird der Test nachgestellt
+//! assert_eq!(relais.random_test(Some(test.seed), 1), test);
+//!
+//! let sequence = relais.random_test(Some(42), 5);
+//! assert_eq!(sequence.frames.len(), 5);
+//! assert_eq!(relais.random_test(Some(42), 5), sequence);
+//! ```
+use rand::{self, IsaacRng, SeedableRng};
+use std::fmt;
+
+
+/// Seed und Frames eines Zufallstests, siehe Modul Dokumentation
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub struct RandomTest {
+    pub seed: u64,
+    /// Ausgegebene Frames im Aufbau von `ShiftRegister.data`
+    pub frames: Vec<Vec<u8>>,
+}
+
+impl RandomTest {
+    /// Erzeugt einen neuen, zufälligen Seed
+    pub fn new_seed() -> u64 {
+        rand::random()
+    }
+
+    /// Zufallsgenerator für den Seed
+    ///
+    /// Es wird bewusst nicht `StdRng` verwendet, dessen Algorithmus hängt von der Plattform ab.
+    /// So lässt sich ein Test vom ARM Board auf dem Entwicklungsrechner nachstellen.
+    pub fn rng(seed: u64) -> IsaacRng {
+        let seed: &[u32] = &[(seed >> 32) as u32, seed as u32];
+        IsaacRng::from_seed(seed)
+    }
+
+    /// Liefert nacheinander `count` Frames aus `frame`
+    ///
+    /// # Arguments
+    /// * `seed`    - Seed des Tests, `None` erzeugt einen neuen Seed
+    /// * `count`   - Anzahl der Frames
+    /// * `frame`   - Erzeugt aus dem Zufallsgenerator einen Frame
+    pub fn generate<F>(seed: Option<u64>, count: usize, mut frame: F) -> Self
+        where F: FnMut(&mut IsaacRng) -> Vec<u8>
+    {
+        let seed = seed.unwrap_or_else(RandomTest::new_seed);
+        let mut rng = RandomTest::rng(seed);
+
+        RandomTest {
+            seed: seed,
+            frames: (0..count).map(|_| frame(&mut rng)).collect(),
+        }
+    }
+}
+
+impl fmt::Display for RandomTest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "Seed {}, {} Frames:", self.seed, self.frames.len()));
+        for frame in &self.frames {
+            try!(write!(f, " ["));
+            // Ausgänge 1 bis 8 stehen in `data[0]`, im Log wird von hinten nach vorne gelesen
+            for (i, byte) in frame.iter().rev().enumerate() {
+                if i > 0 {
+                    try!(write!(f, " "));
+                }
+                try!(write!(f, "{:08b}", byte));
+            }
+            try!(write!(f, "]"));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+//! Rückmeldung der Ausgänge über eine 74HC165 Kette
+//!
+//! Auf manchen Prüfadaptern sind die Ausgänge der 74HC595 Kette auf die Eingänge einer 74HC165
+//! (parallel in, seriell out) Kette zurückgeführt. Ist im Profil ein `readback` hinterlegt, liest
+//! das `ShiftRegister` nach jedem `shift_out` diese Kette ein und vergleicht sie mit `data`. Jeder
+//! Ausgang der nicht dem Sollwert entspricht (klemmt oder ist offen), wird als
+//! `ErrorKind::ReadbackMismatch` gemeldet.
+//!
+//! Ausgang `n` der 74HC595 Kette muss dabei auf Eingang `n` der 74HC165 Kette liegen, `QH` des
+//! letzten 74HC165 hängt am `data_pin`. `CLK INH` ist fest auf low.
+//!
+//! # Examples
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::errors::*;
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let readback = Readback { load_pin: 100, clock_pin: 101, data_pin: 102 };
+//! let mock = RecordingBackend::new();
+//! mock.connect_readback(38, 44, 40, &readback);
+//!
+//! let mut leds = ShiftRegister::with_backend(ShiftRegisterType::LED, Box::new(mock.clone()));
+//! leds.readback = Some(readback);
+//! leds.set(1).unwrap();
+//! leds.set(17).unwrap();
+//! assert_eq!(leds.read_back().unwrap(), vec![0b1, 0, 0b1]);
+//!
+//! // Ausgang 3 klemmt auf low
+//! mock.set_stuck(3, 0);
+//! let err = leds.set(3).unwrap_err();
+//! match *err.kind() {
+//!     ErrorKind::ReadbackMismatch(_, num, _, expected) => assert_eq!((num, expected), (3, true)),
+//!     _ => panic!("Ausgang 3 hätte als fehlerhaft gemeldet werden müssen"),
+//! }
+//! // Ausgegeben wurde der neue Stand trotzdem, er bleibt im `data` Buffer
+//! assert!(err.applied());
+//! assert_eq!(err.severity(), Severity::Warning);
+//! assert_eq!(leds.get(3).unwrap(), true);
+//! assert_eq!(mock.latched_data(38, 44, 40).last(), Some(&vec![0b101, 0, 0b1]));
+//! ```
+
+
+/// Pins der 74HC165 Kette
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct Readback {
+    /// `SH/!LD`, low übernimmt die Eingänge, high schiebt
+    pub load_pin: u64,
+    /// `CLK` der 74HC165 Kette
+    pub clock_pin: u64,
+    /// `QH` des letzten 74HC165 in der Kette
+    pub data_pin: u64,
+}
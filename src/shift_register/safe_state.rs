@@ -0,0 +1,351 @@
+//! Sicherer Zustand der Shift Register beim Beenden, bei Panics und bei Signalen
+//!
+//! Bleibt das Programm z.B. bei aktivem "alle Relais an" stehen oder wird es beendet, bleiben die
+//! Relais sonst angezogen. Jedes `ShiftRegister` das hier mit `register()` angemeldet ist, wird
+//! deshalb in seinen sicheren Zustand (`ShiftRegister.safe_word`) gebracht:
+//!
+//! * beim normalen Beenden über `enter_safe_state_all()`
+//! * beim `Drop` des `ShiftRegister`
+//! * aus dem Panic Hook, siehe `install_handlers()`
+//! * bei SIGINT und SIGTERM, siehe `install_handlers()`
+//!
+//! Dateien die einen geschalteten Stand beschreiben, z.B. die State Datei der Kommandozeile,
+//! werden mit `register_state_file()` angemeldet. Im sicheren Zustand stimmt ihr Stand nicht mehr,
+//! sie werden deshalb gelöscht.
+//!
+//! Hält der panicende Thread den Lock eines Shift Registers, z.B. mitten in einem Shift-Out, kommt
+//! der Panic Hook nicht an das Register. Über dessen `OutputEnable` werden die Ausgänge dann
+//! wenigstens über !OE abgeschaltet. Sobald der Lock nach dem Unwinding vergiftet ist, holt ein
+//! Thread den sicheren Zustand nach.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let mock = RecordingBackend::new();
+//! let mut relais = ShiftRegister::with_backend(ShiftRegisterType::RELAIS, Box::new(mock.clone()));
+//! // Relais 3 ist im sicheren Zustand angezogen
+//! relais.safe_word = Some(vec![0b100, 0]);
+//! let relais = Arc::new(Mutex::new(relais));
+//! safe_state::register(&relais);
+//!
+//! relais.lock().unwrap().all().unwrap();
+//! safe_state::enter_safe_state_all().unwrap();
+//! assert_eq!(relais.lock().unwrap().data, vec![0b100, 0]);
+//! assert_eq!(mock.latched_data(45, 39, 37).last(), Some(&vec![0b100, 0]));
+//! ```
+//!
+//! Panic während der Lock gehalten wird:
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//! use std::thread;
+//! use std::time::Duration;
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let mock = RecordingBackend::new();
+//! let mut relais = ShiftRegister::with_backend(ShiftRegisterType::RELAIS, Box::new(mock.clone()));
+//! let oe = relais.oe_pin.unwrap();
+//! relais.all().unwrap();
+//! let relais = Arc::new(Mutex::new(relais));
+//! safe_state::register(&relais);
+//! safe_state::install_handlers();
+//!
+//! mock.clear();
+//! let panicked = {
+//!     let relais = relais.clone();
+//!     thread::spawn(move || {
+//!         let _locked = relais.lock().unwrap();
+//!         panic!("Panic mit gehaltenem Lock");
+//!     }).join()
+//! };
+//! assert!(panicked.is_err());
+//! // Noch während der Lock gehalten wurde, ist !OE high gegangen
+//! assert_eq!(mock.values(oe).first(), Some(&1));
+//!
+//! // Nach dem Unwinding ist der Lock vergiftet, der sichere Zustand wird nachgeholt
+//! for _ in 0..100 {
+//!     if relais.lock().unwrap_or_else(|err| err.into_inner()).data == vec![0, 0] {
+//!         break;
+//!     }
+//!     thread::sleep(Duration::from_millis(10));
+//! }
+//! assert_eq!(mock.latched_data(45, 39, 37).last(), Some(&vec![0, 0]));
+//! ```
+use errors::*;
+use libc;
+use shift_register::gpio_session::GpioSession;
+use shift_register::pwm::{PwmConfig, SoftPwm};
+use shift_register::shift_register::ShiftRegister;
+use std::fs;
+use std::io::Write;
+use std::panic;
+use std::path::PathBuf;
+use std::process;
+use std::sync::{Arc, Mutex, MutexGuard, Once, ONCE_INIT, TryLockError, Weak};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+
+/// Wie oft nach einem Panic versucht wird, den Lock eines Shift Registers zu bekommen
+const RETRY_ATTEMPTS: u32 = 100;
+/// Abstand der Versuche in Millisekunden
+const RETRY_INTERVAL_MS: u64 = 10;
+
+lazy_static! {
+    /// Alle angemeldeten Shift Register, jeweils mit ihrem !OE Zugriff
+    static ref REGISTRY: Mutex<Vec<(Weak<Mutex<ShiftRegister>>, Weak<OutputEnable>)>> = Mutex::new(vec![]);
+    /// Dateien mit einem geschalteten Stand, siehe `register_state_file()`
+    static ref STATE_FILES: Mutex<Vec<PathBuf>> = Mutex::new(vec![]);
+}
+
+/// Zuletzt empfangenes Signal, `0` solange keins empfangen wurde
+static SIGNAL: AtomicUsize = AtomicUsize::new(0);
+
+static INSTALL: Once = ONCE_INIT;
+
+/// Meldet das Shift Register an, es wird beim Beenden in den sicheren Zustand gebracht
+///
+/// Es wird nur eine schwache Referenz gehalten, verworfene Shift Register fallen automatisch
+/// wieder raus.
+pub fn register(shift_register: &Arc<Mutex<ShiftRegister>>) {
+    let output_enable = shift_register.lock().unwrap_or_else(|err| err.into_inner()).output_enable();
+    let mut registry = REGISTRY.lock().unwrap_or_else(|err| err.into_inner());
+    registry.retain(|&(ref entry, _)| entry.upgrade().is_some());
+    registry.push((Arc::downgrade(shift_register), Arc::downgrade(&output_enable)));
+}
+
+/// Bringt alle angemeldeten Shift Register in den sicheren Zustand
+///
+/// Es werden immer alle Shift Register versucht, geliefert wird der erste aufgetretene Fehler.
+pub fn enter_safe_state_all() -> Result<()> {
+    apply(true)
+}
+
+/// Meldet eine Datei an, die den geschalteten Stand der Ausgänge beschreibt
+///
+/// Sobald ein Shift Register in den sicheren Zustand geht, wird die Datei gelöscht.
+///
+/// # Examples
+///
+/// ```
+/// use xmz_mod_touch_test_tool::shift_register::*;
+///
+/// let path = ::std::env::temp_dir().join("xmz-safe-state-doctest.state.json");
+/// ::std::fs::File::create(&path).unwrap();
+/// safe_state::register_state_file(&path);
+///
+/// let mut relais = ShiftRegister::with_backend(ShiftRegisterType::RELAIS, Box::new(RecordingBackend::new()));
+/// relais.set(1).unwrap();
+/// assert!(path.exists());
+/// relais.enter_safe_state().unwrap();
+/// assert!(!path.exists());
+/// ```
+pub fn register_state_file<P: Into<PathBuf>>(path: P) {
+    let path = path.into();
+    let mut state_files = STATE_FILES.lock().unwrap_or_else(|err| err.into_inner());
+    if !state_files.contains(&path) {
+        state_files.push(path);
+    }
+}
+
+/// Löscht alle mit `register_state_file()` angemeldeten Dateien, Fehler werden nur geloggt
+pub fn remove_state_files() {
+    let state_files = STATE_FILES.lock().unwrap_or_else(|err| err.into_inner());
+    for path in state_files.iter() {
+        match fs::remove_file(path) {
+            Ok(()) => {},
+            Err(ref err) if err.kind() == ::std::io::ErrorKind::NotFound => {},
+            Err(err) => warn!("State Datei {} konnte nicht gelöscht werden: {}", path.display(), err),
+        }
+    }
+}
+
+/// Installiert den Panic Hook und die Signal Handler für SIGINT und SIGTERM
+///
+/// Bei einem Panic werden alle Shift Register in den sicheren Zustand gebracht, danach läuft der
+/// bisherige Panic Hook. Bei SIGINT/SIGTERM wird das Programm nach dem sicheren Zustand mit
+/// `128 + Signal` beendet. Mehrfaches Aufrufen installiert die Handler nur einmal.
+pub fn install_handlers() {
+    INSTALL.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            // Im Panic Hook darf nicht blockiert werden, der panicende Thread hält evtl. den Lock
+            if let Err(err) = apply(false) {
+                let _ = writeln!(::std::io::stderr(), "Sicherer Zustand fehlgeschlagen: {}", err);
+            }
+            default_hook(info);
+        }));
+
+        unsafe {
+            libc::signal(libc::SIGINT, on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t);
+            libc::signal(libc::SIGTERM, on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t);
+        }
+        // Im Signal Handler selbst darf kein Mutex angefasst werden, der Thread erledigt das
+        thread::spawn(|| {
+            loop {
+                let signal = SIGNAL.load(Ordering::SeqCst);
+                if signal != 0 {
+                    if let Err(err) = enter_safe_state_all() {
+                        let _ = writeln!(::std::io::stderr(), "Sicherer Zustand fehlgeschlagen: {}", err);
+                    }
+                    process::exit(128 + signal as i32);
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+    });
+}
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    SIGNAL.store(signal as usize, Ordering::SeqCst);
+}
+
+/// Bringt alle angemeldeten Shift Register in den sicheren Zustand
+///
+/// # Arguments
+/// * `blocking`    - `false` schaltet Shift Register deren Lock gerade gehalten wird nur über
+///                   !OE ab, der sichere Zustand wird nach dem Unwinding nachgeholt
+fn apply(blocking: bool) -> Result<()> {
+    let registry = match (blocking, REGISTRY.try_lock()) {
+        (_, Ok(registry)) => registry.clone(),
+        (_, Err(TryLockError::Poisoned(err))) => err.into_inner().clone(),
+        (true, Err(TryLockError::WouldBlock)) => REGISTRY.lock().unwrap_or_else(|err| err.into_inner()).clone(),
+        (false, Err(TryLockError::WouldBlock)) => return Ok(()),
+    };
+
+    let mut result = Ok(());
+    for &(ref entry, ref output_enable) in &registry {
+        let shift_register = match entry.upgrade() {
+            Some(shift_register) => shift_register,
+            None => continue,
+        };
+        let step = match (blocking, shift_register.try_lock()) {
+            (_, Ok(shift_register)) => enter(shift_register),
+            (_, Err(TryLockError::Poisoned(err))) => enter(err.into_inner()),
+            (true, Err(TryLockError::WouldBlock)) => enter(shift_register.lock().unwrap_or_else(|err| err.into_inner())),
+            (false, Err(TryLockError::WouldBlock)) => {
+                retry_after_unwind(entry.clone());
+                match output_enable.upgrade() {
+                    Some(output_enable) => output_enable.disable(),
+                    None => Ok(()),
+                }
+            },
+        };
+        if let Err(err) = step {
+            if result.is_ok() {
+                result = Err(err);
+            }
+        }
+    }
+
+    result
+}
+
+fn enter(mut shift_register: MutexGuard<ShiftRegister>) -> Result<()> {
+    shift_register.enter_safe_state()
+}
+
+/// Holt den sicheren Zustand nach, sobald der Lock frei bzw. nach dem Unwinding vergiftet ist
+///
+/// Endet der Prozess mit dem Unwinding, z.B. bei einem Panic im Main Thread, bleiben die Ausgänge
+/// über !OE abgeschaltet.
+fn retry_after_unwind(shift_register: Weak<Mutex<ShiftRegister>>) {
+    thread::spawn(move || {
+        for _ in 0..RETRY_ATTEMPTS {
+            thread::sleep(Duration::from_millis(RETRY_INTERVAL_MS));
+            let shift_register = match shift_register.upgrade() {
+                Some(shift_register) => shift_register,
+                None => return,
+            };
+            let result = match shift_register.try_lock() {
+                Ok(shift_register) => enter(shift_register),
+                Err(TryLockError::Poisoned(err)) => enter(err.into_inner()),
+                Err(TryLockError::WouldBlock) => continue,
+            };
+            if let Err(err) = result {
+                let _ = writeln!(::std::io::stderr(), "Sicherer Zustand fehlgeschlagen: {}", err);
+            }
+            return;
+        }
+    });
+}
+
+/// Zugriff auf den !OE Pin eines Shift Registers, ohne dessen Lock
+///
+/// Das `ShiftRegister` hinterlegt hier seine offene Session, den !OE Pin bzw. den PWM Kanal und
+/// eine laufende Software PWM. Der sichere Zustand kann die Ausgänge so auch dann abschalten,
+/// wenn der Lock des Shift Registers gerade gehalten wird, siehe Modul Dokumentation.
+#[derive(Debug, Default)]
+pub struct OutputEnable {
+    line: Mutex<OutputEnableLine>,
+}
+
+#[derive(Debug, Default)]
+struct OutputEnableLine {
+    session: Option<Arc<Mutex<GpioSession>>>,
+    oe_pin: Option<u64>,
+    pwm: Option<PwmConfig>,
+    /// `running` Flag der Software PWM
+    soft_pwm: Option<Arc<AtomicBool>>,
+}
+
+impl OutputEnable {
+    pub fn new() -> Self {
+        OutputEnable::default()
+    }
+
+    /// Hinterlegt die offene Session, `None` beim Schließen der Session
+    ///
+    /// # Arguments
+    /// * `session`     - Session über die der !OE Pin geschaltet wird
+    /// * `oe_pin`      - !OE Pin
+    /// * `pwm`         - PWM Kanal am !OE Pin, dann gehört der Pin nicht zur Session
+    pub fn set_session(&self, session: Option<Arc<Mutex<GpioSession>>>, oe_pin: Option<u64>, pwm: Option<PwmConfig>) {
+        let mut line = self.lock();
+        line.session = session;
+        line.oe_pin = oe_pin;
+        line.pwm = pwm;
+        line.soft_pwm = None;
+    }
+
+    /// Hinterlegt das `running` Flag der laufenden Software PWM, `None` wenn keine läuft
+    pub fn set_soft_pwm(&self, running: Option<Arc<AtomicBool>>) {
+        self.lock().soft_pwm = running;
+    }
+
+    /// Schaltet alle Ausgänge über !OE ab
+    ///
+    /// Wird nur der Lock des `ShiftRegister` gehalten, klappt das immer. Hält der Thread
+    /// gerade den Lock der Session, wird ein Fehler geliefert.
+    pub fn disable(&self) -> Result<()> {
+        let line = match self.line.try_lock() {
+            Ok(line) => line,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => bail!("!OE wird gerade umgeschaltet"),
+        };
+        if let Some(ref running) = line.soft_pwm {
+            SoftPwm::halt(running);
+        }
+        if let Some(ref pwm) = line.pwm {
+            return pwm.set_high_ratio(100);
+        }
+        if let (Some(ref session), Some(oe_pin)) = (line.session.as_ref(), line.oe_pin) {
+            let mut session = match session.try_lock() {
+                Ok(session) => session,
+                Err(TryLockError::Poisoned(err)) => err.into_inner(),
+                Err(TryLockError::WouldBlock) => bail!("Die GPIO Session wird gerade verwendet"),
+            };
+            try!(session.set_value(oe_pin, 1));
+        }
+
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<OutputEnableLine> {
+        self.line.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
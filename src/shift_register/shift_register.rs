@@ -1,8 +1,21 @@
 use errors::*;
-use rand::Rng;
+use rand::{IsaacRng, Rng};
+use shift_register::colour::Colour;
+use shift_register::cycle_counter::CycleCounter;
+use shift_register::diagnostic::{Diagnostic, DiagnosticPattern};
+use shift_register::gpio_backend::{GpioBackend, GpioDriver};
+use shift_register::gpio_session::GpioSession;
+use shift_register::interlock::Interlock;
+use shift_register::pwm::{PwmConfig, SoftPwm};
+use shift_register::random_test::RandomTest;
+use shift_register::readback::Readback;
+use shift_register::safe_state::{self, OutputEnable};
+use shift_register::simulated_chain::SimulatedChain;
+use shift_register::spi_driver::{OutputDriver, SpiBus, SpidevBus};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::thread;
 use std::time::Duration;
-use sysfs_gpio::{Direction, Pin};
 
 
 #[derive(Clone)]
@@ -10,10 +23,10 @@ use sysfs_gpio::{Direction, Pin};
 pub enum ShiftRegisterType {
     LED,
     RELAIS,
+    /// 64 Ausgänge an einer virtuellen 74HC595 Kette, siehe `GpioDriver::Simulated`
     Simulation,
 }
 
-#[derive(Clone)]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ShiftRegister {
     register_type: ShiftRegisterType,
@@ -21,8 +34,73 @@ pub struct ShiftRegister {
     pub ds_pin: Option<u64>,
     pub clock_pin: Option<u64>,
     pub latch_pin: Option<u64>,
+    /// Anzahl der hintereinander geschalteten 8bit Shift Register
+    pub chips: usize,
+    /// Anzahl der tatsächlich vorhandenen Ausgänge
+    pub outputs: usize,
+    /// Bezeichnung der Ausgänge, `labels[0]` gehört zu Ausgang 1
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Farbe der Ausgänge in der GUI, `colours[0]` gehört zu Ausgang 1
+    #[serde(default)]
+    pub colours: Vec<Colour>,
+    /// Spalte und Zeile der Ausgänge in der GUI, `positions[0]` gehört zu Ausgang 1
+    #[serde(default)]
+    pub positions: Vec<(i32, i32)>,
+    /// Ein Byte pro Shift Register, `data[0]` enthält die Ausgänge 1 bis 8
+    #[serde(default)]
+    pub data: Vec<u8>,
+    /// GPIO Backend über das die Pins geschaltet werden
+    #[serde(default)]
+    pub gpio: GpioDriver,
+    /// Art der Datenausgabe, Bit Banging über die GPIO Pins oder Hardware SPI
+    #[serde(default)]
+    pub driver: OutputDriver,
+    /// PWM Kanal am !OE Pin, ohne PWM Kanal wird zum Dimmen eine Software PWM verwendet
+    #[serde(default)]
+    pub pwm: Option<PwmConfig>,
+    /// 74HC165 Kette über die die Ausgänge nach jedem `shift_out` zurückgelesen werden
+    #[serde(default)]
+    pub readback: Option<Readback>,
+    /// Sicherer Zustand, ein Byte pro Shift Register wie `data`, ohne Angabe sind alle Ausgänge aus
+    #[serde(default)]
+    pub safe_word: Option<Vec<u8>>,
+    /// Gruppen von Ausgängen von denen höchstens einer gleichzeitig an sein darf
+    #[serde(default)]
+    pub interlocks: Vec<Interlock>,
+    /// Schaltspiele der Ausgänge
     #[serde(default)]
-    pub data: u64,
+    pub cycle_counter: CycleCounter,
+    /// Zugriff auf die Pins, wird anhand von `gpio` beim ersten Schreiben geöffnet
+    #[serde(skip_serializing, skip_deserializing)]
+    backend: Option<Box<GpioBackend>>,
+    /// Exportierte Pins, bleibt bis zum `close_session()` bzw. `Drop` bestehen
+    #[serde(skip_serializing, skip_deserializing)]
+    session: Option<Arc<Mutex<GpioSession>>>,
+    /// `true` wenn alle Ausgänge über !OE abgeschaltet sind
+    #[serde(skip_serializing, skip_deserializing)]
+    blanked: bool,
+    /// Helligkeit in Prozent, `None` entspricht 100%
+    #[serde(skip_serializing, skip_deserializing)]
+    brightness: Option<u8>,
+    /// Laufende Software PWM am !OE Pin
+    #[serde(skip_serializing, skip_deserializing)]
+    soft_pwm: Option<SoftPwm>,
+    /// `true` sobald seit dem Öffnen der Session ein kompletter Stand ausgegeben wurde
+    #[serde(skip_serializing, skip_deserializing)]
+    latched: bool,
+    /// Zugriff auf !OE für den sicheren Zustand, siehe `output_enable()`
+    #[serde(skip_serializing, skip_deserializing)]
+    output_enable: Arc<OutputEnable>,
+    /// Geöffneter SPI Bus, wird bei `OutputDriver::Spi` beim ersten Schreiben geöffnet
+    #[serde(skip_serializing, skip_deserializing)]
+    spi: Option<Box<SpiBus>>,
+    /// Virtuelle 74HC595 Kette bei `GpioDriver::Simulated`
+    #[serde(skip_serializing, skip_deserializing)]
+    simulated_chain: Option<SimulatedChain>,
+    /// Empfänger der Zustandsänderungen, siehe `subscribe()`
+    #[serde(skip_serializing, skip_deserializing)]
+    subscribers: Vec<Sender<Vec<u8>>>,
 }
 
 impl Default for ShiftRegister {
@@ -33,7 +111,29 @@ impl Default for ShiftRegister {
             ds_pin: None,
             clock_pin: None,
             latch_pin: None,
-            data: 0,
+            chips: 8,
+            outputs: 64,
+            labels: vec![],
+            colours: vec![],
+            positions: vec![],
+            data: vec![0; 8],
+            gpio: GpioDriver::None,
+            driver: OutputDriver::BitBang,
+            pwm: None,
+            readback: None,
+            safe_word: None,
+            interlocks: vec![],
+            cycle_counter: CycleCounter::default(),
+            backend: None,
+            session: None,
+            blanked: false,
+            brightness: None,
+            soft_pwm: None,
+            latched: false,
+            output_enable: Arc::new(OutputEnable::new()),
+            spi: None,
+            simulated_chain: None,
+            subscribers: vec![],
         }
     }
 }
@@ -47,38 +147,341 @@ impl ShiftRegister {
     /// # Examples
     ///
     /// ```
-    /// use xmz_server::*;
+    /// use xmz_mod_touch_test_tool::shift_register::*;
     ///
     /// let sim = ShiftRegister::new(ShiftRegisterType::Simulation);
-    /// assert_eq!(sim.data, 0b0);
+    /// assert_eq!(sim.data, vec![0; 8]);
     /// ```
     pub fn new(register_type: ShiftRegisterType) -> Self {
+        // `ShiftRegister` implementiert `Drop`, deshalb wird der Default angepasst anstatt
+        // `..Default::default()` zu verwenden
+        let mut shift_register = ShiftRegister::default();
         match register_type {
-            ShiftRegisterType::LED => ShiftRegister {
-                register_type: register_type,
-                oe_pin: Some(276),
-                ds_pin: Some(38),
-                clock_pin: Some(44),
-                latch_pin: Some(40),
-                data: 0,
+            ShiftRegisterType::LED => {
+                shift_register.oe_pin = Some(276);
+                shift_register.ds_pin = Some(38);
+                shift_register.clock_pin = Some(44);
+                shift_register.latch_pin = Some(40);
+                shift_register.chips = 3;
+                shift_register.outputs = 20;
+                shift_register.data = vec![0; 3];
+                // Anordnung der Front: oben 4 Status LED, darunter 4 Module mit je 4 LED, die
+                // erste LED eines Moduls unten
+                let (mut colours, mut positions) = (vec![], vec![]);
+                for (column, colour) in [1, 3, 5, 7].iter().zip(&[Colour::Green, Colour::Yellow, Colour::Red, Colour::Red]) {
+                    colours.push(*colour);
+                    positions.push((*column, 0));
+                }
+                for column in &[0, 2, 4, 6] {
+                    for (row, colour) in [7, 5, 3, 1].iter().zip(&[Colour::Green, Colour::Yellow, Colour::Red, Colour::Red]) {
+                        colours.push(*colour);
+                        positions.push((*column, *row));
+                    }
+                }
+                shift_register.colours = colours;
+                shift_register.positions = positions;
+                shift_register.gpio = GpioDriver::Sysfs;
             },
-            ShiftRegisterType::RELAIS => ShiftRegister {
-                register_type: register_type,
-                oe_pin: Some(277),
-                ds_pin: Some(45),
-                clock_pin: Some(39),
-                latch_pin: Some(37),
-                data: 0,
+            ShiftRegisterType::RELAIS => {
+                shift_register.oe_pin = Some(277);
+                shift_register.ds_pin = Some(45);
+                shift_register.clock_pin = Some(39);
+                shift_register.latch_pin = Some(37);
+                shift_register.chips = 2;
+                shift_register.outputs = 9;
+                shift_register.data = vec![0; 2];
+                shift_register.gpio = GpioDriver::Sysfs;
             },
-            ShiftRegisterType::Simulation => ShiftRegister {
-                register_type: register_type,
-                oe_pin: None,
-                ds_pin: None,
-                clock_pin: None,
-                latch_pin: None,
-                data: 0,
+            ShiftRegisterType::Simulation => {
+                // Virtuelle Pin Nummern der `SimulatedChain`
+                shift_register.oe_pin = Some(1);
+                shift_register.ds_pin = Some(2);
+                shift_register.clock_pin = Some(3);
+                shift_register.latch_pin = Some(4);
+                shift_register.gpio = GpioDriver::Simulated;
+            },
+        }
+        shift_register.register_type = register_type;
+
+        shift_register
+    }
+
+    /// Erzeugt ein neues Shift Register, das seine Pins über das übergebene Backend ansteuert
+    ///
+    /// Die Pin Nummern werden wie bei `new()` aus dem `register_type` übernommen.
+    ///
+    /// # Arguments
+    /// * `register_type`     - Art des Shift Registers
+    /// * `backend`           - GPIO Backend über das die Pins geschaltet werden
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xmz_mod_touch_test_tool::shift_register::*;
+    ///
+    /// let leds = ShiftRegister::with_backend(ShiftRegisterType::LED, Box::new(RecordingBackend::new()));
+    /// assert_eq!(leds.ds_pin, Some(38));
+    /// ```
+    pub fn with_backend(register_type: ShiftRegisterType, backend: Box<GpioBackend>) -> Self {
+        let mut shift_register = ShiftRegister::new(register_type);
+        shift_register.set_backend(backend);
+
+        shift_register
+    }
+
+    /// Tauscht das GPIO Backend aus, `gpio` wird dann nicht mehr beachtet
+    ///
+    /// # Arguments
+    /// * `backend`           - GPIO Backend über das die Pins geschaltet werden
+    pub fn set_backend(&mut self, backend: Box<GpioBackend>) {
+        // Pins des alten Backends freigeben
+        self.soft_pwm = None;
+        self.output_enable.set_session(None, None, None);
+        self.session = None;
+        self.backend = Some(backend);
+    }
+
+    /// Gibt die Pins frei, beim nächsten Schreiben werden sie erneut exportiert
+    ///
+    /// Normalerweise bleiben die Pins so lange exportiert wie das `ShiftRegister` existiert.
+    pub fn close_session(&mut self) -> Result<()> {
+        self.cycle_counter.flush();
+        // Die Software PWM und `output_enable` halten ebenfalls eine Referenz auf die Session
+        self.soft_pwm = None;
+        self.output_enable.set_session(None, None, None);
+        if let Some(session) = self.session.take() {
+            if let Ok(session) = Arc::try_unwrap(session) {
+                let session = session.into_inner().unwrap_or_else(|err| err.into_inner());
+                self.backend = Some(try!(session.close()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Zugriff auf den !OE Pin, der ohne den Lock des `ShiftRegister` auskommt
+    ///
+    /// Wird von `safe_state::register()` hinterlegt.
+    pub fn output_enable(&self) -> Arc<OutputEnable> {
+        self.output_enable.clone()
+    }
+
+    /// Gibt die Daten über den übergebenen SPI Bus aus, anstatt sie über DS/CLOCK einzutakten
+    ///
+    /// Normalerweise wird der Bus anhand von `driver` selbst geöffnet. Diese Funktion ist für
+    /// eigene Busse gedacht, z.B. den `LoopbackBus` in Tests.
+    ///
+    /// # Arguments
+    /// * `spi`               - SPI Bus über den die Daten ausgegeben werden
+    pub fn set_spi_bus(&mut self, spi: Box<SpiBus>) {
+        self.spi = Some(spi);
+    }
+
+    /// Art des Shift Registers
+    pub fn register_type(&self) -> &ShiftRegisterType {
+        &self.register_type
+    }
+
+    /// Virtuelle 74HC595 Kette, an die das Shift Register bei `GpioDriver::Simulated` ausgibt
+    ///
+    /// Die Kette wird beim ersten Aufruf angelegt, geliefert wird ein Klon der die selbe Kette
+    /// zeigt. Ohne `GpioDriver::Simulated` oder ohne DS/Clock/Latch Pin wird `None` geliefert.
+    /// Siehe `shift_register::simulated_chain`.
+    pub fn simulated_chain(&mut self) -> Option<SimulatedChain> {
+        if self.gpio != GpioDriver::Simulated {
+            return None;
+        }
+        if self.simulated_chain.is_none() {
+            if let (Some(ds_pin), Some(clock_pin), Some(latch_pin)) = (self.ds_pin, self.clock_pin, self.latch_pin) {
+                // Mit PWM Kanal hängt !OE nicht an der Kette
+                let oe_pin = match self.pwm {
+                    Some(_) => None,
+                    None => self.oe_pin,
+                };
+                self.simulated_chain = Some(SimulatedChain::new(self.chips, ds_pin, clock_pin, latch_pin, oe_pin));
+            }
+        }
+
+        self.simulated_chain.clone()
+    }
+
+    /// Bezeichnung des Ausgangs `num` (Eins basiert)
+    ///
+    /// Ist in `labels` keine Bezeichnung hinterlegt, wird sie aus der Art des Shift Registers und
+    /// der Nummer gebildet.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xmz_mod_touch_test_tool::shift_register::*;
+    ///
+    /// let mut relais = ShiftRegister::new(ShiftRegisterType::RELAIS);
+    /// relais.labels = vec!["Alarm 1".to_string()];
+    /// assert_eq!(relais.label(1), "Alarm 1");
+    /// assert_eq!(relais.label(2), "Relais 2");
+    /// ```
+    pub fn label(&self, num: u64) -> String {
+        match self.labels.get((num as usize).wrapping_sub(1)) {
+            Some(label) => label.clone(),
+            None => {
+                match self.register_type {
+                    ShiftRegisterType::LED => format!("Led {}", num),
+                    ShiftRegisterType::RELAIS => format!("Relais {}", num),
+                    ShiftRegisterType::Simulation => format!("Ausgang {}", num),
+                }
+            },
+        }
+    }
+
+    /// Farbe des Ausgangs `num` (Eins basiert), `None` wenn in `colours` keine hinterlegt ist
+    pub fn colour(&self, num: u64) -> Option<Colour> {
+        self.colours.get((num as usize).wrapping_sub(1)).cloned()
+    }
+
+    /// Spalte und Zeile des Ausgangs `num` (Eins basiert) in der GUI
+    ///
+    /// Ist in `positions` keine Position hinterlegt, liegen die Ausgänge in der Zeile unterhalb
+    /// der hinterlegten Positionen nebeneinander.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xmz_mod_touch_test_tool::shift_register::*;
+    ///
+    /// let mut relais = ShiftRegister::new(ShiftRegisterType::RELAIS);
+    /// assert_eq!(relais.grid_position(1), (0, 0));
+    /// assert_eq!(relais.grid_position(9), (8, 0));
+    ///
+    /// relais.positions = vec![(0, 0), (0, 1)];
+    /// assert_eq!(relais.grid_position(2), (0, 1));
+    /// assert_eq!(relais.grid_position(3), (2, 2));
+    /// ```
+    pub fn grid_position(&self, num: u64) -> (i32, i32) {
+        let index = (num as usize).wrapping_sub(1);
+        match self.positions.get(index) {
+            Some(position) => *position,
+            None => {
+                let row = self.positions.iter().map(|&(_, row)| row + 1).max().unwrap_or(0);
+                (index as i32, row)
+            },
+        }
+    }
+
+    /// Prüft ob die Beschreibung des Shift Registers in sich stimmig ist
+    ///
+    /// Wird nach dem Laden eines Hardware Profils aufgerufen.
+    pub fn validate(&self) -> Result<()> {
+        if self.outputs > self.chips * 8 {
+            bail!(ErrorKind::InvalidProfile(format!("{:?}: {} Ausgänge passen nicht in {} Shift Register",
+                                                    self.register_type, self.outputs, self.chips)));
+        }
+        if self.labels.len() > self.outputs {
+            bail!(ErrorKind::InvalidProfile(format!("{:?}: {} Bezeichnungen für {} Ausgänge",
+                                                    self.register_type, self.labels.len(), self.outputs)));
+        }
+        if self.colours.len() > self.outputs {
+            bail!(ErrorKind::InvalidProfile(format!("{:?}: {} Farben für {} Ausgänge",
+                                                    self.register_type, self.colours.len(), self.outputs)));
+        }
+        if self.positions.len() > self.outputs {
+            bail!(ErrorKind::InvalidProfile(format!("{:?}: {} Positionen für {} Ausgänge",
+                                                    self.register_type, self.positions.len(), self.outputs)));
+        }
+        if let Some(ref safe_word) = self.safe_word {
+            if safe_word.len() != self.chips {
+                bail!(ErrorKind::InvalidProfile(format!("{:?}: Sicherer Zustand mit {} Bytes für {} Shift Register",
+                                                        self.register_type, safe_word.len(), self.chips)));
+            }
+        }
+        for interlock in &self.interlocks {
+            if let Some(num) = interlock.outputs.iter().find(|num| **num == 0 || **num > self.outputs as u64) {
+                bail!(ErrorKind::InvalidProfile(format!("{:?}: Verriegelung {} mit ungültigem Ausgang {}",
+                                                        self.register_type, interlock.name, num)));
+            }
+        }
+        if let GpioDriver::Cdev { ref lines, .. } = self.gpio {
+            let pins: Vec<u64> = self.pins().into_iter().chain(self.input_pins()).collect();
+            if let Some(line) = lines.iter().find(|line| !pins.contains(&line.pin)) {
+                bail!(ErrorKind::InvalidProfile(format!("{:?}: GPIO Line {} für Pin {}, der nicht verwendet wird",
+                                                        self.register_type, line.offset, line.pin)));
+            }
+        }
+        if self.gpio == GpioDriver::Simulated && (self.ds_pin.is_none() || self.clock_pin.is_none() || self.latch_pin.is_none()) {
+            bail!(ErrorKind::InvalidProfile(format!("{:?}: Die virtuelle 74HC595 Kette braucht DS, Clock und Latch Pin",
+                                                    self.register_type)));
+        }
+        try!(self.check_interlocks(&self.safe_word())
+            .chain_err(|| ErrorKind::InvalidProfile(format!("{:?}: Sicherer Zustand verletzt eine Verriegelung",
+                                                            self.register_type))));
+
+        Ok(())
+    }
+
+    /// `true` wenn die Daten über SPI und nicht per Bit Banging ausgegeben werden
+    fn uses_spi(&self) -> bool {
+        match self.driver {
+            OutputDriver::Spi { .. } => true,
+            OutputDriver::BitBang => self.spi.is_some(),
+        }
+    }
+
+    /// Byte Index und Bitmaske des Ausgangs `num` (Eins basiert) im `data` Buffer
+    ///
+    /// Liefert `ErrorKind::InvalidChannel` wenn es den Ausgang `num` nicht gibt.
+    fn position(&self, num: u64) -> Result<(usize, u8)> {
+        if num == 0 || num > self.outputs as u64 {
+            bail!(ErrorKind::InvalidChannel(format!("{:?}", self.register_type), num, self.outputs));
+        }
+        let index = (num - 1) as usize;
+
+        Ok((index / 8, 1 << (index % 8)))
+    }
+
+    /// Liefert `ErrorKind::InterlockViolation` wenn `data` eine der Verriegelungen verletzt
+    fn check_interlocks(&self, data: &[u8]) -> Result<()> {
+        for interlock in &self.interlocks {
+            let active = interlock.active(data);
+            if active.len() > 1 {
+                bail!(ErrorKind::InterlockViolation(format!("{:?}", self.register_type),
+                                                    interlock.name.clone(),
+                                                    active.iter().map(|num| self.label(*num)).collect()));
             }
         }
+
+        Ok(())
+    }
+
+    /// Alle vorhandenen Ausgänge an, ohne die Verriegelungen zu verletzen, siehe `all()`
+    ///
+    /// Geliefert werden der Zustand und die Ausgänge die wegen einer Verriegelung aus bleiben.
+    fn all_outputs(&self) -> (Vec<u8>, Vec<u64>) {
+        let mut data = self.outputs_mask();
+        let mut skipped = vec![];
+        for interlock in &self.interlocks {
+            skipped.extend(interlock.keep_lowest(&mut data));
+        }
+        skipped.sort();
+
+        (data, skipped)
+    }
+
+    /// Bitmaske aller tatsächlich vorhandenen Ausgänge, ein Byte pro Shift Register
+    fn outputs_mask(&self) -> Vec<u8> {
+        (0..self.chips)
+            .map(|chip| {
+                match self.outputs.saturating_sub(chip * 8) {
+                    0 => 0,
+                    n if n >= 8 => 0xff,
+                    n => (1u8 << n) - 1,
+                }
+            })
+            .collect()
+    }
+
+    /// Passt die Länge des `data` Buffers an die Anzahl der Shift Register an
+    fn resize_data(&mut self) {
+        let chips = self.chips;
+        self.data.resize(chips, 0);
     }
 
     /// Setzt das übergebene Bit im Shift Register `data` Buffer
@@ -87,24 +490,29 @@ impl ShiftRegister {
     /// * `num`     - Nummer des zu setzenden Bits **Diese Nummer ist Eins basiert!**
     ///
     /// Der Parameter ist nicht Null basiert. Das bedeutet `set(1)` setzt das erste Bit(0) im `data`
-    /// Buffer.
+    /// Buffer. Gibt es den Ausgang nicht, wird `ErrorKind::InvalidChannel` geliefert.
     ///
     /// # Examples
     ///
     /// ```
-    /// use xmz_server::*;
+    /// use xmz_mod_touch_test_tool::shift_register::*;
     ///
     /// let mut sim = ShiftRegister::new(ShiftRegisterType::Simulation);
-    /// assert_eq!(sim.data, 0b0);
+    /// assert_eq!(sim.data[0], 0b0);
     /// sim.set(3);
-    /// assert_eq!(sim.data, 0b100);
+    /// assert_eq!(sim.data[0], 0b100);
+    /// sim.set(10);
+    /// assert_eq!(sim.data[1], 0b10);
+    ///
+    /// assert!(sim.set(0).is_err());
+    /// assert!(sim.set(65).is_err());
     /// ```
     /// More info: http://stackoverflow.com/questions/47981/how-do-you-set-clear-and-toggle-a-single-bit-in-c-c
     pub fn set(&mut self, num: u64) -> Result<()> {
-        self.data |= 1 << num -1;
-        try!(self.shift_out());
+        let mut transaction = self.transaction();
+        try!(transaction.set(num));
 
-        Ok(())
+        transaction.commit()
     }
 
     /// Abfrage ob ein Bit gesetzt ist, `true` wenn ja, `false` wenn das bit nicht gesetzt ist
@@ -118,20 +526,21 @@ impl ShiftRegister {
     /// # Examples
     ///
     /// ```
-    /// use xmz_server::*;
+    /// use xmz_mod_touch_test_tool::shift_register::*;
     ///
     /// let mut sim = ShiftRegister::new(ShiftRegisterType::Simulation);
     /// sim.set(1);
     /// sim.set(3);
-    /// assert_eq!(sim.get(1), true);
-    /// assert_eq!(sim.get(2), false);
-    /// assert_eq!(sim.get(3), true);
+    /// assert_eq!(sim.get(1).unwrap(), true);
+    /// assert_eq!(sim.get(2).unwrap(), false);
+    /// assert_eq!(sim.get(3).unwrap(), true);
     /// ```
     /// More info: http://stackoverflow.com/questions/47981/how-do-you-set-clear-and-toggle-a-single-bit-in-c-c
-    pub fn get(&self, num: u64) -> bool {
-        match (self.data >> num -1) & 1 {
-            0 => false,
-            _ => true,
+    pub fn get(&self, num: u64) -> Result<bool> {
+        let (byte, bit) = try!(self.position(num));
+        match self.data.get(byte).cloned().unwrap_or(0) & bit {
+            0 => Ok(false),
+            _ => Ok(true),
         }
     }
 
@@ -146,25 +555,25 @@ impl ShiftRegister {
     /// # Examples
     ///
     /// ```
-    /// use xmz_server::*;
+    /// use xmz_mod_touch_test_tool::shift_register::*;
     ///
     /// let mut sim = ShiftRegister::new(ShiftRegisterType::Simulation);
-    /// assert_eq!(sim.data, 0b0);
+    /// assert_eq!(sim.data[0], 0b0);
     ///
     /// sim.set(1);
     /// sim.set(3);
-    /// assert_eq!(sim.get(1), true);
-    /// assert_eq!(sim.get(3), true);
+    /// assert_eq!(sim.get(1).unwrap(), true);
+    /// assert_eq!(sim.get(3).unwrap(), true);
     ///
     /// sim.clear(3);
-    /// assert_eq!(sim.get(1), true);
-    /// assert_eq!(sim.get(3), false);
+    /// assert_eq!(sim.get(1).unwrap(), true);
+    /// assert_eq!(sim.get(3).unwrap(), false);
     /// ```
     pub fn clear(&mut self, num: u64) -> Result<()> {
-        self.data &= !(1 << num - 1);
-        try!(self.shift_out());
+        let mut transaction = self.transaction();
+        try!(transaction.clear(num));
 
-        Ok(())
+        transaction.commit()
     }
 
     /// Schaltet das übergebene Bit um, war es Null dann wird es Eins und umgekehrt
@@ -178,21 +587,21 @@ impl ShiftRegister {
     /// # Examples
     ///
     /// ```
-    /// use xmz_server::*;
+    /// use xmz_mod_touch_test_tool::shift_register::*;
     ///
     /// let mut sim = ShiftRegister::new(ShiftRegisterType::Simulation);
-    /// assert_eq!(sim.data, 0b0);
+    /// assert_eq!(sim.data[0], 0b0);
     ///
     /// sim.toggle(3);
-    /// assert_eq!(sim.get(3), true);
+    /// assert_eq!(sim.get(3).unwrap(), true);
     /// sim.toggle(3);
-    /// assert_eq!(sim.get(3), false);
+    /// assert_eq!(sim.get(3).unwrap(), false);
     /// ```
     pub fn toggle(&mut self, num: u64) -> Result<()> {
-        self.data ^= 1 << num -1;
-        try!(self.shift_out());
+        let mut transaction = self.transaction();
+        try!(transaction.toggle(num));
 
-        Ok(())
+        transaction.commit()
     }
 
     /// Reset nullt den Datenspeicher und gleicht ihn mit der Hardware ab.
@@ -200,39 +609,129 @@ impl ShiftRegister {
     /// # Examples
     ///
     /// ```
-    /// use xmz_server::*;
+    /// use xmz_mod_touch_test_tool::shift_register::*;
     ///
     /// let mut sim = ShiftRegister::new(ShiftRegisterType::Simulation);
-    /// assert_eq!(sim.get(1), false);
+    /// assert_eq!(sim.get(1).unwrap(), false);
     /// sim.set(1);
-    /// assert_eq!(sim.get(1), true);
+    /// assert_eq!(sim.get(1).unwrap(), true);
     /// sim.reset();
-    /// assert_eq!(sim.get(1), false);
+    /// assert_eq!(sim.get(1).unwrap(), false);
     /// ```
     pub fn reset(&mut self) -> Result<()> {
-        self.data = 0;
-        try!(self.shift_out());
+        let data = vec![0; self.chips];
 
-        Ok(())
+        self.commit(data)
     }
 
-    /// Alle Shift Register werden high gezogen
+    /// Alle vorhandenen Ausgänge werden high gezogen
+    ///
+    /// Von jeder Verriegelung bleibt nur der niedrigste Ausgang an. Die übrigen Ausgänge der
+    /// Verriegelungen bleiben aus, sie werden geliefert und ins Log geschrieben.
     ///
     /// # Examples
     ///
     /// ```
-    /// use xmz_server::*;
+    /// use xmz_mod_touch_test_tool::shift_register::*;
     ///
     /// let mut sim = ShiftRegister::new(ShiftRegisterType::Simulation);
-    /// assert_eq!(sim.get(1), false);
+    /// assert_eq!(sim.get(1).unwrap(), false);
     /// sim.all();
-    /// assert_eq!(sim.get(1), true);
+    /// assert_eq!(sim.get(1).unwrap(), true);
+    ///
+    /// // Das Relais Board hat nur 9 Ausgänge, verteilt auf 2 Shift Register
+    /// let mut relais = ShiftRegister::new(ShiftRegisterType::RELAIS);
+    /// relais.set_backend(Box::new(RecordingBackend::new()));
+    /// assert!(relais.all().unwrap().is_empty());
+    /// assert_eq!(relais.data, vec![0xff, 0b1]);
     /// ```
-    pub fn all(&mut self) -> Result<()> {
-        self.data = u64::max_value();
-        try!(self.shift_out());
+    pub fn all(&mut self) -> Result<Vec<u64>> {
+        let (data, skipped) = self.all_outputs();
+        if !skipped.is_empty() {
+            let skipped: Vec<String> = skipped.iter().map(|num| self.label(*num)).collect();
+            info!("{:?} alle an, wegen Verriegelung aus: {}", self.register_type, skipped.join(", "));
+        }
+        try!(self.commit(data));
 
-        Ok(())
+        Ok(skipped)
+    }
+
+    /// Schreibt den kompletten `data` Buffer auf einmal, mit einem einzigen Shift-Out
+    ///
+    /// # Arguments
+    /// * `data`    - Ein Byte pro Shift Register, `data[0]` enthält die Ausgänge 1 bis 8
+    ///
+    /// Fehlende Bytes werden mit Null aufgefüllt. Ist ein Ausgang gesetzt den es nicht gibt,
+    /// wird `ErrorKind::InvalidChannel` geliefert und nichts geschrieben.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xmz_mod_touch_test_tool::shift_register::*;
+    ///
+    /// let mut relais = ShiftRegister::new(ShiftRegisterType::RELAIS);
+    /// relais.set_backend(Box::new(RecordingBackend::new()));
+    /// relais.write(vec![0b101]).unwrap();
+    /// assert_eq!(relais.data, vec![0b101, 0]);
+    ///
+    /// // Relais 10 gibt es nicht
+    /// assert!(relais.write(vec![0, 0b10]).is_err());
+    /// assert_eq!(relais.data, vec![0b101, 0]);
+    /// ```
+    pub fn write(&mut self, data: Vec<u8>) -> Result<()> {
+        let mask = self.outputs_mask();
+        for (index, byte) in data.iter().enumerate() {
+            let invalid = byte & !mask.get(index).cloned().unwrap_or(0);
+            if invalid != 0 {
+                let num = (index * 8) as u64 + invalid.trailing_zeros() as u64 + 1;
+                bail!(ErrorKind::InvalidChannel(format!("{:?}", self.register_type), num, self.outputs));
+            }
+        }
+
+        self.commit(data)
+    }
+
+    /// Beginnt eine Transaktion
+    ///
+    /// Die Bit Operationen der Transaktion werden gesammelt und erst mit `commit()` in einem
+    /// einzigen Shift-Out übernommen. So gibt es an den Ausgängen keine Zwischenzustände. Wird die
+    /// Transaktion ohne `commit()` verworfen, bleibt das Shift Register unverändert.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xmz_mod_touch_test_tool::shift_register::*;
+    ///
+    /// let mock = RecordingBackend::new();
+    /// let mut relais = ShiftRegister::with_backend(ShiftRegisterType::RELAIS, Box::new(mock.clone()));
+    /// let (ds, clock, latch) = (45, 39, 37);
+    ///
+    /// {
+    ///     let mut transaction = relais.transaction();
+    ///     transaction.set(1).unwrap();
+    ///     transaction.set(2).unwrap();
+    ///     transaction.set(9).unwrap();
+    ///     transaction.clear(2).unwrap();
+    ///     transaction.commit().unwrap();
+    /// }
+    /// // Nur ein einziger Latch mit dem Endzustand
+    /// assert_eq!(mock.latched_data(ds, clock, latch), vec![vec![0b1, 0b1]]);
+    ///
+    /// {
+    ///     let mut transaction = relais.transaction();
+    ///     transaction.reset();
+    ///     // kein commit()
+    /// }
+    /// assert_eq!(relais.data, vec![0b1, 0b1]);
+    /// ```
+    pub fn transaction<'a>(&'a mut self) -> Transaction<'a> {
+        self.resize_data();
+        let data = self.data.clone();
+
+        Transaction {
+            register: self,
+            data: data,
+        }
     }
 
     /// Lampentest testet alle Outputs, Reset nach 1Sek
@@ -240,10 +739,12 @@ impl ShiftRegister {
     /// Diese Funktion schaltet alle Ausgänge high, wartet eine Sekunde und schaltet danach alle
     /// Ausgänge wieder aus.
     ///
+    /// Die Funktion blockiert, aus der GUI heraus stattdessen den `PatternRunner` verwenden.
+    ///
     /// # Examples
     ///
     /// ```
-    /// use xmz_server::*;
+    /// use xmz_mod_touch_test_tool::shift_register::*;
     ///
     /// let mut sim = ShiftRegister::new(ShiftRegisterType::Simulation);
     ///
@@ -251,15 +752,14 @@ impl ShiftRegister {
     /// ```
     pub fn test(&mut self) -> Result<()> {
         // Alten Stand speichern
-        let old_state = self.data;
-        // Buffer komplett mit Einsen füllen
-        self.data = u64::max_value();
-        try!(self.shift_out());
+        let old_state = self.data.clone();
+        // Alle vorhandenen Ausgänge mit Einsen füllen
+        let data = self.outputs_mask();
+        try!(self.commit(data));
         thread::sleep(Duration::new(1, 0));
         try!(self.reset());
         // alten Stand wieder herstellen
-        self.data = old_state;
-        try!(self.shift_out());
+        try!(self.commit(old_state));
 
         Ok(())
     }
@@ -269,10 +769,12 @@ impl ShiftRegister {
     /// Diese Funktion schaltet alle Ausgänge high, wartet eine Sekunde und schaltet danach alle
     /// Ausgänge wieder aus.
     ///
+    /// Die Funktion blockiert, aus der GUI heraus stattdessen den `PatternRunner` verwenden.
+    ///
     /// # Examples
     ///
     /// ```
-    /// use xmz_server::*;
+    /// use xmz_mod_touch_test_tool::shift_register::*;
     ///
     /// let mut sim = ShiftRegister::new(ShiftRegisterType::Simulation);
     ///
@@ -280,127 +782,521 @@ impl ShiftRegister {
     /// ```
     pub fn test_timed(&mut self) -> Result<()> {
         // Alten Stand speichern
-        let old_state = self.data;
-        // Buffer komplett mit Einsen füllen
-        self.data = u64::max_value();
-        try!(self.shift_out());
+        let old_state = self.data.clone();
+        // Alle vorhandenen Ausgänge mit Einsen füllen
+        let data = self.outputs_mask();
+        try!(self.commit(data));
         thread::sleep(Duration::new(1, 0));
         try!(self.reset());
         // alten Stand wieder herstellen
-        self.data = old_state;
-        try!(self.shift_out());
+        try!(self.commit(old_state));
 
         Ok(())
     }
 
     /// Random Lampentest testet einige, Outputs, zufällig
     ///
-    /// Diese Funktion setzt das ShiftRegister in einen zufälligen Zustand
+    /// Diese Funktion setzt das ShiftRegister in einen zufälligen Zustand. Seed und Frame werden
+    /// geliefert und ins Log geschrieben, siehe `random_test`.
+    ///
+    /// # Arguments
+    /// * `seed`      - Seed eines früheren Tests, `None` erzeugt einen neuen Seed
     ///
     /// # Examples
     ///
     /// ```
-    /// use xmz_server::*;
+    /// use xmz_mod_touch_test_tool::shift_register::*;
     ///
     /// let mut sim = ShiftRegister::new(ShiftRegisterType::Simulation);
     ///
-    /// sim.test_random();
+    /// sim.test_random(None);
     /// ```
-    pub fn test_random(&mut self) -> Result<()> {
-        // Buffer mit Zufallsdaten füllen
-        self.data =  ::rand::thread_rng().gen_range(1, u64::max_value());
+    pub fn test_random(&mut self, seed: Option<u64>) -> Result<RandomTest> {
+        // Vorhandene Ausgänge mit Zufallsdaten füllen
+        let test = self.random_test(seed, 1);
+        try!(self.commit(test.frames[0].clone()));
 
-        try!(self.shift_out());
-
-        Ok(())
+        Ok(test)
     }
 
     /// Random Lampentest testet einige, Outputs, zufällig, Reset nach 1Sek
     ///
-    /// Diese Funktion speichert den Zustand des Shift Registers, schaltet einige,
-    /// zufällige Ausgänge high, wartet eine Sekunde und schaltet danach alle
-    /// Ausgänge wieder auf den vorherigen Zustand.
+    /// Diese Funktion speichert den Zustand des Shift Registers, gibt nacheinander `frames`
+    /// zufällige Zustände für jeweils eine Sekunde aus und schaltet danach alle Ausgänge wieder auf
+    /// den vorherigen Zustand. Seed und Frames werden geliefert und ins Log geschrieben.
+    ///
+    /// Die Funktion blockiert, aus der GUI heraus stattdessen den `PatternRunner` mit
+    /// `Pattern::Frames` aus `random_test()` verwenden.
+    ///
+    /// # Arguments
+    /// * `seed`      - Seed eines früheren Tests, `None` erzeugt einen neuen Seed
+    /// * `frames`    - Anzahl der zufälligen Zustände
     ///
     /// # Examples
     ///
     /// ```
-    /// use xmz_server::*;
+    /// use xmz_mod_touch_test_tool::shift_register::*;
     ///
     /// let mut sim = ShiftRegister::new(ShiftRegisterType::Simulation);
     ///
-    /// sim.test_random_timed();
+    /// sim.test_random_timed(None, 1);
     /// ```
-    pub fn test_random_timed(&mut self) -> Result<()> {
+    pub fn test_random_timed(&mut self, seed: Option<u64>, frames: usize) -> Result<RandomTest> {
         // Alten Stand speichern
-        let old_state = self.data;
-        // Buffer mit Zufallsdaten füllen
-        self.data =  ::rand::thread_rng().gen_range(1, u64::max_value());
-        try!(self.shift_out());
-        thread::sleep(Duration::new(1, 0));
+        let old_state = self.data.clone();
+        // Vorhandene Ausgänge mit Zufallsdaten füllen
+        let test = self.random_test(seed, frames);
+        for frame in &test.frames {
+            try!(self.commit(frame.clone()));
+            thread::sleep(Duration::new(1, 0));
+        }
         try!(self.reset());
         // alten Stand wieder herstellen
-        self.data = old_state;
-        try!(self.shift_out());
+        try!(self.commit(old_state));
+
+        Ok(test)
+    }
+
+    /// Erzeugt `frames` zufällige Zustände aus `seed`, ohne sie auszugeben
+    ///
+    /// Jeder Zustand enthält nur vorhandene Ausgänge, mindestens ein Ausgang ist gesetzt. Von jeder
+    /// verletzten Verriegelung bleibt nur ein zufällig gewählter Ausgang an. Seed und Frames werden
+    /// ins Log geschrieben.
+    ///
+    /// # Arguments
+    /// * `seed`      - Seed eines früheren Tests, `None` erzeugt einen neuen Seed
+    /// * `frames`    - Anzahl der zufälligen Zustände
+    pub fn random_test(&self, seed: Option<u64>, frames: usize) -> RandomTest {
+        let test = RandomTest::generate(seed, frames, |rng| self.random_data(rng));
+        info!("{:?} Zufallstest, {}", self.register_type, test);
+
+        test
+    }
+
+    /// Schaltet alle Ausgänge über den !OE Pin ab, der `data` Buffer bleibt dabei erhalten
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xmz_mod_touch_test_tool::shift_register::*;
+    ///
+    /// let mock = RecordingBackend::new();
+    /// let mut leds = ShiftRegister::with_backend(ShiftRegisterType::LED, Box::new(mock.clone()));
+    /// let oe = leds.oe_pin.unwrap();
+    ///
+    /// leds.set(1).unwrap();
+    /// leds.disable_outputs().unwrap();
+    /// assert_eq!(mock.value(oe), Some(1));
+    /// assert_eq!(leds.get(1).unwrap(), true);
+    ///
+    /// leds.enable_outputs().unwrap();
+    /// assert_eq!(mock.value(oe), Some(0));
+    /// ```
+    pub fn disable_outputs(&mut self) -> Result<()> {
+        self.blanked = true;
+        try!(self.update_output_enable());
+        self.notify();
+
+        Ok(())
+    }
+
+    /// Schaltet die Ausgänge über den !OE Pin wieder frei
+    pub fn enable_outputs(&mut self) -> Result<()> {
+        self.blanked = false;
+        try!(self.update_output_enable());
+        self.notify();
 
         Ok(())
     }
 
+    /// `false` wenn die Ausgänge mit `disable_outputs()` abgeschaltet wurden
+    pub fn outputs_enabled(&self) -> bool {
+        !self.blanked
+    }
 
-    /// Exportiert die Pins in das sysfs des Linux Kernels
+    /// Dimmt die Ausgänge per PWM am !OE Pin
+    ///
+    /// Ist in `pwm` ein PWM Kanal hinterlegt, wird dieser verwendet, ansonsten wird der !OE Pin
+    /// von einer Software PWM getaktet.
     ///
-    fn export_pins(&self) -> Result<()> {
-        if let Some(oe_pin) = self.oe_pin { try!(Pin::new(oe_pin).export()) };
-        if let Some(ds_pin) = self.ds_pin { try!(Pin::new(ds_pin).export()) };
-        if let Some(clock_pin) = self.clock_pin { try!(Pin::new(clock_pin).export()) };
-        if let Some(latch_pin) = self.latch_pin { try!(Pin::new(latch_pin).export()) };
+    /// # Arguments
+    /// * `percent`     - Helligkeit von `0` (aus) bis `100` (voll an)
+    pub fn set_brightness(&mut self, percent: u8) -> Result<()> {
+        self.brightness = match percent {
+            percent if percent >= 100 => None,
+            percent => Some(percent),
+        };
+        try!(self.update_output_enable());
+        self.notify();
 
         Ok(())
     }
 
-    /// Schaltet die Pins in den OUTPUT Pin Modus
+    /// Aktuelle Helligkeit in Prozent
+    pub fn brightness(&self) -> u8 {
+        self.brightness.unwrap_or(100)
+    }
+
+    /// Sicherer Zustand der Ausgänge, nicht vorhandene Ausgänge sind immer aus
+    pub fn safe_word(&self) -> Vec<u8> {
+        let mut safe_word = self.safe_word.clone().unwrap_or(vec![]);
+        safe_word.resize(self.chips, 0);
+        safe_word.iter()
+            .zip(self.outputs_mask())
+            .map(|(byte, mask)| byte & mask)
+            .collect()
+    }
+
+    /// Bringt die Ausgänge in den sicheren Zustand (`safe_word()`)
+    ///
+    /// Eine laufende Dimmung wird beendet und die Ausgänge werden freigegeben, damit der sichere
+    /// Zustand auch tatsächlich anliegt. Kann der sichere Zustand nicht ausgegeben werden und sind
+    /// dabei alle Ausgänge aus, werden die Ausgänge wenigstens über !OE abgeschaltet. Angemeldete
+    /// State Dateien werden gelöscht, siehe `safe_state::register_state_file()`.
     ///
-    fn set_pin_direction_output(&self) -> Result<()> {
-        if let Some(oe_pin) = self.oe_pin { try!(Pin::new(oe_pin).set_direction(Direction::Out)) };
-        if let Some(oe_pin) = self.oe_pin { try!(Pin::new(oe_pin).set_value(0)) }; // !OE pin low == Shift register enabled.
-        if let Some(ds_pin) = self.ds_pin { try!(Pin::new(ds_pin).set_direction(Direction::Out)) };
-        if let Some(ds_pin) = self.ds_pin { try!(Pin::new(ds_pin).set_value(0)) };
-        if let Some(clock_pin) = self.clock_pin { try!(Pin::new(clock_pin).set_direction(Direction::Out)) };
-        if let Some(clock_pin) = self.clock_pin { try!(Pin::new(clock_pin).set_value(0)) };
-        if let Some(latch_pin) = self.latch_pin { try!(Pin::new(latch_pin).set_direction(Direction::Out)) };
-        if let Some(latch_pin) = self.latch_pin { try!(Pin::new(latch_pin).set_value(0)) };
+    /// # Examples
+    ///
+    /// ```
+    /// use xmz_mod_touch_test_tool::shift_register::*;
+    ///
+    /// let mock = RecordingBackend::new();
+    /// {
+    ///     let mut leds = ShiftRegister::with_backend(ShiftRegisterType::LED, Box::new(mock.clone()));
+    ///     leds.all().unwrap();
+    /// }
+    /// // Beim Drop werden alle LED wieder ausgeschaltet
+    /// assert_eq!(mock.latched_data(38, 44, 40).last(), Some(&vec![0, 0, 0]));
+    /// ```
+    pub fn enter_safe_state(&mut self) -> Result<()> {
+        safe_state::remove_state_files();
+        let safe_word = self.safe_word();
+        let old_state = ::std::mem::replace(&mut self.data, safe_word.clone());
+        if let Err(err) = self.shift_out() {
+            if safe_word.iter().all(|byte| *byte == 0) {
+                self.blanked = true;
+                let _ = self.update_output_enable();
+            }
+            self.cycle_counter.flush();
+            return Err(err);
+        }
+        self.notify();
+        self.count_cycles(&old_state);
+        self.cycle_counter.flush();
+        self.blanked = false;
+        self.brightness = None;
+        try!(self.update_output_enable());
 
         Ok(())
     }
 
+    /// Liest den Zustand der Ausgänge über die 74HC165 Kette ein
+    ///
+    /// Das Ergebnis hat den selben Aufbau wie der `data` Buffer. Ohne `readback` Konfiguration
+    /// oder ohne Session wird der `data` Buffer geliefert.
+    pub fn read_back(&mut self) -> Result<Vec<u8>> {
+        try!(self.open_session());
+        let readback = match (self.readback.clone(), self.session.is_some()) {
+            (Some(readback), true) => readback,
+            _ => return Ok(self.data.clone()),
+        };
+
+        // Eingänge parallel übernehmen, danach liegt Bit 7 des letzten Shift Registers an QH
+        try!(self.set_pin_value(Some(readback.load_pin), 0));
+        try!(self.set_pin_value(Some(readback.load_pin), 1));
+        let mut data = vec![0; self.chips];
+        for i in (0..self.chips * 8).rev() {
+            data[i / 8] |= try!(self.get_pin_value(readback.data_pin)) << (i % 8);
+            try!(self.set_pin_value(Some(readback.clock_pin), 1));
+            try!(self.set_pin_value(Some(readback.clock_pin), 0));
+        }
+
+        Ok(data)
+    }
+
+    /// Vergleicht die zurückgelesenen Ausgänge mit dem `data` Buffer
+    ///
+    /// Der erste Ausgang der nicht dem Sollwert entspricht wird als `ErrorKind::ReadbackMismatch`
+    /// gemeldet. Nicht vorhandene Ausgänge werden nicht verglichen.
+    pub fn verify(&mut self) -> Result<()> {
+        if let Some(num) = try!(self.mismatches()).first() {
+            let (index, mask) = try!(self.position(*num));
+            bail!(ErrorKind::ReadbackMismatch(format!("{:?}", self.register_type), *num,
+                                              self.label(*num), self.data[index] & mask != 0));
+        }
+
+        Ok(())
+    }
+
+    /// Alle Ausgänge (Eins basiert) deren Rückmeldung nicht dem `data` Buffer entspricht
+    pub fn mismatches(&mut self) -> Result<Vec<u64>> {
+        let actual = try!(self.read_back());
+        let mut mismatches = vec![];
+        for num in 1..(self.outputs as u64 + 1) {
+            let (index, mask) = try!(self.position(num));
+            if self.data[index] & mask != actual[index] & mask {
+                mismatches.push(num);
+            }
+        }
+
+        Ok(mismatches)
+    }
+
+    /// Beginnt einen schrittweisen Durchlauf des Diagnose Musters, siehe `diagnostic`
+    pub fn diagnostic(&self, pattern: DiagnosticPattern) -> Diagnostic {
+        Diagnostic::new(pattern, self.outputs, &self.interlocks)
+    }
+
+    /// Meldet einen Empfänger für Zustandsänderungen an
+    ///
+    /// Nach jeder Ausgabe wird der neue `data` Buffer an alle Empfänger geschickt, egal ob die
+    /// Änderung über die Bit API, einen Test, ein Muster oder den sicheren Zustand kam. Auch nach
+    /// dem Ab- und Freischalten oder Dimmen über !OE wird der `data` Buffer geschickt. Wird der
+    /// `Receiver` verworfen, fällt der Empfänger automatisch wieder raus.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xmz_mod_touch_test_tool::shift_register::*;
+    ///
+    /// let mut relais = ShiftRegister::new(ShiftRegisterType::RELAIS);
+    /// relais.gpio = GpioDriver::None;
+    /// let changes = relais.subscribe();
+    ///
+    /// relais.set(1).unwrap();
+    /// relais.all().unwrap();
+    /// assert_eq!(changes.try_recv(), Ok(vec![0b1, 0]));
+    /// assert_eq!(changes.try_recv(), Ok(vec![0xff, 0b1]));
+    /// assert!(changes.try_recv().is_err());
+    /// ```
+    pub fn subscribe(&mut self) -> Receiver<Vec<u8>> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+
+        receiver
+    }
+
+
+    /// Übernimmt den neuen Stand in den `data` Buffer und gibt ihn an die Hardware aus
+    ///
+    /// Alle Funktionen die den Zustand der Ausgänge ändern, laufen hier zusammen.
+    ///
+    /// Ist eine Rückmeldung konfiguriert, wird der neue Stand danach mit `verify()` geprüft.
+    /// Abgeschaltete oder gedimmte Ausgänge liefern keine verlässliche Rückmeldung, in dem Fall
+    /// wird nicht geprüft. Eine abweichende Rückmeldung wird erst geliefert nachdem der neue Stand
+    /// übernommen und gemeldet wurde, die Ausgänge sind dann ja geschaltet (`Error::applied()`).
+    /// Schlägt dagegen schon die Ausgabe fehl, bleibt der alte Stand im `data` Buffer.
+    fn commit(&mut self, data: Vec<u8>) -> Result<()> {
+        try!(self.check_interlocks(&data));
+        let old_state = ::std::mem::replace(&mut self.data, data);
+        self.resize_data();
+        if let Err(err) = self.shift_out() {
+            self.data = old_state;
+            return Err(err);
+        }
+        self.notify();
+        self.count_cycles(&old_state);
+        if self.readback.is_some() && !self.blanked && self.brightness.is_none() {
+            try!(self.verify());
+        }
+
+        Ok(())
+    }
+
+    /// Zählt die Schaltspiele gegenüber `old_state`, nur die Relais verschleißen
+    fn count_cycles(&mut self, old_state: &[u8]) {
+        if let ShiftRegisterType::RELAIS = self.register_type {
+            self.cycle_counter.count(old_state, &self.data);
+        }
+    }
+
+    /// Schickt den aktuellen `data` Buffer an alle Empfänger, verworfene Empfänger fallen raus
+    fn notify(&mut self) {
+        let data = self.data.clone();
+        self.subscribers.retain(|subscriber| subscriber.send(data.clone()).is_ok());
+    }
+
+    /// Liefert alle konfigurierten Ausgangs Pins
+    ///
+    /// Bei der Ausgabe über SPI gehören DS und CLOCK dem SPI Controller, sie werden dann nicht
+    /// über das Backend angefasst. Das selbe gilt für den !OE Pin wenn ein PWM Kanal hinterlegt
+    /// ist.
+    fn pins(&self) -> Vec<u64> {
+        // Mit PWM Kanal gehört der !OE Pin dem PWM Controller
+        let oe_pin = match self.pwm {
+            Some(_) => None,
+            None => self.oe_pin,
+        };
+        let pins = match self.uses_spi() {
+            true => [oe_pin, None, None, self.latch_pin],
+            false => [oe_pin, self.ds_pin, self.clock_pin, self.latch_pin],
+        };
+        let mut pins: Vec<u64> = pins.iter()
+            .filter_map(|pin| *pin)
+            .collect();
+        if let Some(ref readback) = self.readback {
+            pins.push(readback.load_pin);
+            pins.push(readback.clock_pin);
+        }
+
+        pins
+    }
+
+    /// Liefert alle Pins die als Eingang gelesen werden
+    fn input_pins(&self) -> Vec<u64> {
+        match self.readback {
+            Some(ref readback) => vec![readback.data_pin],
+            None => vec![],
+        }
+    }
+
+    /// Setzt den Pin über die Session, ohne Pin oder ohne Session passiert nix
+    fn set_pin_value(&mut self, pin: Option<u64>, value: u8) -> Result<()> {
+        if let (Some(pin), Some(ref session)) = (pin, self.session.as_ref()) {
+            try!(session.lock().unwrap().set_value(pin, value));
+        }
+
+        Ok(())
+    }
+
+    /// Liest den Pin über die Session, ohne Session wird `0` geliefert
+    fn get_pin_value(&mut self, pin: u64) -> Result<u8> {
+        match self.session {
+            Some(ref session) => session.lock().unwrap().get_value(pin),
+            None => Ok(0),
+        }
+    }
+
+    /// Zufälliger Zustand der vorhandenen Ausgänge, siehe `random_test()`
+    fn random_data(&self, rng: &mut IsaacRng) -> Vec<u8> {
+        let mask = self.outputs_mask();
+        loop {
+            let mut data: Vec<u8> = mask.iter().map(|mask| rng.gen::<u8>() & mask).collect();
+            for interlock in &self.interlocks {
+                let mut active = interlock.active(&data);
+                if active.len() > 1 {
+                    let keep = rng.gen_range(0, active.len());
+                    active.remove(keep);
+                    for num in active {
+                        let index = (num - 1) as usize;
+                        data[index / 8] &= !(1 << (index % 8));
+                    }
+                }
+            }
+            if data.iter().any(|byte| *byte != 0) || self.outputs == 0 {
+                return data;
+            }
+        }
+    }
+
+    /// Öffnet die Session, die Pins werden dabei exportiert und als Ausgang konfiguriert
+    ///
+    /// Ist die Session bereits offen, passiert nix. Ohne Backend wird keine Session geöffnet.
+    fn open_session(&mut self) -> Result<()> {
+        if self.session.is_some() {
+            return Ok(());
+        }
+        let backend = match (self.backend.take(), self.simulated_chain()) {
+            (Some(backend), _) => Some(backend),
+            (None, Some(chain)) => Some(Box::new(chain) as Box<GpioBackend>),
+            (None, None) => try!(self.gpio.open()),
+        };
+        if let Some(backend) = backend {
+            let (outputs, inputs) = (self.pins(), self.input_pins());
+            let session = try!(GpioSession::open_with_inputs(backend, outputs, inputs, self.oe_pin));
+            self.session = Some(Arc::new(Mutex::new(session)));
+            self.output_enable.set_session(self.session.clone(), self.oe_pin, self.pwm.clone());
+            // Die Session startet mit abgeschalteten Ausgängen (!OE high), freigegeben wird erst
+            // nach der ersten kompletten Ausgabe, siehe `shift_out()`
+            self.latched = false;
+            if self.pwm.is_some() {
+                try!(self.update_output_enable());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Schaltet den !OE Pin entsprechend `blanked` und `brightness`
+    fn update_output_enable(&mut self) -> Result<()> {
+        try!(self.open_session());
+        self.soft_pwm = None;
+        self.output_enable.set_soft_pwm(None);
+
+        // Anteil der Periode in dem !OE high ist, die Ausgänge also aus sind. Vor der ersten
+        // Ausgabe liegt in den Shift Registern noch ein zufälliger Stand.
+        let percent_high = match (self.blanked || !self.latched, self.brightness) {
+            (true, _) => 100,
+            (false, Some(brightness)) => 100 - brightness,
+            (false, None) => 0,
+        };
+
+        if let Some(ref pwm) = self.pwm {
+            return pwm.set_high_ratio(percent_high);
+        }
+        let oe_pin = self.oe_pin;
+        match percent_high {
+            0 => self.set_pin_value(oe_pin, 0),
+            100 => self.set_pin_value(oe_pin, 1),
+            percent_high => {
+                if let (Some(pin), Some(session)) = (oe_pin, self.session.as_ref()) {
+                    let soft_pwm = SoftPwm::start(session.clone(), pin, percent_high);
+                    self.output_enable.set_soft_pwm(Some(soft_pwm.running()));
+                    self.soft_pwm = Some(soft_pwm);
+                }
+                Ok(())
+            },
+        }
+    }
+
 
     /// Toogelt den Clock Pin high->low
-    fn clock_in(&self) -> Result<()> {
-        if let Some(clock_pin) = self.clock_pin { try!(Pin::new(clock_pin).set_value(1)) };
-        if let Some(clock_pin) = self.clock_pin { try!(Pin::new(clock_pin).set_value(0)) };
+    fn clock_in(&mut self) -> Result<()> {
+        let clock_pin = self.clock_pin;
+        try!(self.set_pin_value(clock_pin, 1));
+        try!(self.set_pin_value(clock_pin, 0));
 
         Ok(())
     }
 
     /// Toggelt den Latch Pin pin high->low,
-    fn latch_out(&self) -> Result<()> {
-        if let Some(latch_pin) = self.latch_pin { try!(Pin::new(latch_pin).set_value(1)) };
-        if let Some(latch_pin) = self.latch_pin { try!(Pin::new(latch_pin).set_value(0)) };
+    fn latch_out(&mut self) -> Result<()> {
+        let latch_pin = self.latch_pin;
+        try!(self.set_pin_value(latch_pin, 1));
+        try!(self.set_pin_value(latch_pin, 0));
 
         Ok(())
     }
 
     /// Schiebt die kompletten Daten in die Schiebe Register und schaltet die Ausgänge dieser
     /// Schiebe Register (latch out)
-    fn shift_out(&self) -> Result<()> {
-        // Wenn export_pins erfolgreich ist werden die Daten eingeclocked, ansonsten passiert nix
-        try!(self.export_pins());
-        try!(self.set_pin_direction_output());
-
-        // Daten einclocken
-        for i in (0..64).rev() {
-            match (self.data >> i) & 1 {
-                1 => { if let Some(ds_pin) = self.ds_pin { try!(Pin::new(ds_pin).set_value(1)) } },
-                _ => { if let Some(ds_pin) = self.ds_pin { try!(Pin::new(ds_pin).set_value(0)) } },
+    ///
+    /// Nach der ersten Ausgabe einer Session werden die Ausgänge über !OE freigegeben.
+    fn shift_out(&mut self) -> Result<()> {
+        // Wenn open_session erfolgreich ist werden die Daten eingeclocked, ansonsten passiert nix
+        try!(self.open_session());
+
+        match self.uses_spi() {
+            true => try!(self.shift_out_spi()),
+            false => try!(self.shift_out_bits()),
+        }
+        if !self.latched {
+            self.latched = true;
+            try!(self.update_output_enable());
+        }
+
+        Ok(())
+    }
+
+    /// Taktet die kompletten Daten Bit für Bit über DS/CLOCK ein und schaltet danach die Ausgänge
+    /// (latch out)
+    fn shift_out_bits(&mut self) -> Result<()> {
+        // Daten einclocken, das höchstwertige Bit des letzten Shift Registers zuerst
+        self.resize_data();
+        let ds_pin = self.ds_pin;
+        for i in (0..self.chips * 8).rev() {
+            match (self.data[i / 8] >> (i % 8)) & 1 {
+                1 => try!(self.set_pin_value(ds_pin, 1)),
+                _ => try!(self.set_pin_value(ds_pin, 0)),
             }
             try!(self.clock_in());
         }
@@ -409,5 +1305,98 @@ impl ShiftRegister {
         Ok(())
     }
 
+    /// Gibt die kompletten Daten in einem SPI Transfer aus und schaltet danach die Ausgänge
+    /// (latch out)
+    fn shift_out_spi(&mut self) -> Result<()> {
+        if self.spi.is_none() {
+            if let OutputDriver::Spi { ref device, speed_hz, loopback } = self.driver {
+                let mut spi = try!(SpidevBus::open(device, speed_hz));
+                spi.set_loopback(loopback);
+                self.spi = Some(Box::new(spi));
+            }
+        }
+
+        // Das Byte des letzten Shift Registers zuerst, wie beim Einclocken per Bit Banging
+        self.resize_data();
+        let bytes: Vec<u8> = self.data.iter().rev().cloned().collect();
+        if let Some(ref mut spi) = self.spi {
+            try!(spi.transfer(&bytes));
+        }
+        try!(self.latch_out());
 
+        Ok(())
+    }
+}
+
+impl Drop for ShiftRegister {
+    /// Bringt die Ausgänge in den sicheren Zustand, wenn sie geschaltet wurden
+    fn drop(&mut self) {
+        if self.session.is_some() {
+            // Fehler können hier nur noch ignoriert werden
+            let _ = self.enter_safe_state();
+        }
+        self.cycle_counter.flush();
+    }
+}
+
+/// Gesammelte Bit Operationen, siehe `ShiftRegister::transaction()`
+pub struct Transaction<'a> {
+    register: &'a mut ShiftRegister,
+    data: Vec<u8>,
+}
+
+impl<'a> Transaction<'a> {
+    /// Setzt den Ausgang `num` (Eins basiert)
+    pub fn set(&mut self, num: u64) -> Result<()> {
+        let (byte, bit) = try!(self.register.position(num));
+        self.data[byte] |= bit;
+
+        Ok(())
+    }
+
+    /// Löscht den Ausgang `num` (Eins basiert)
+    pub fn clear(&mut self, num: u64) -> Result<()> {
+        let (byte, bit) = try!(self.register.position(num));
+        self.data[byte] &= !bit;
+
+        Ok(())
+    }
+
+    /// Schaltet den Ausgang `num` (Eins basiert) um
+    pub fn toggle(&mut self, num: u64) -> Result<()> {
+        let (byte, bit) = try!(self.register.position(num));
+        self.data[byte] ^= bit;
+
+        Ok(())
+    }
+
+    /// Abfrage ob der Ausgang `num` (Eins basiert) in der Transaktion gesetzt ist
+    pub fn get(&self, num: u64) -> Result<bool> {
+        let (byte, bit) = try!(self.register.position(num));
+
+        Ok(self.data[byte] & bit != 0)
+    }
+
+    /// Löscht alle Ausgänge
+    pub fn reset(&mut self) {
+        for byte in self.data.iter_mut() {
+            *byte = 0;
+        }
+    }
+
+    /// Setzt alle vorhandenen Ausgänge, liefert die Ausgänge die wegen einer Verriegelung aus
+    /// bleiben, siehe `ShiftRegister::all()`
+    pub fn all(&mut self) -> Vec<u64> {
+        let (data, skipped) = self.register.all_outputs();
+        self.data = data;
+
+        skipped
+    }
+
+    /// Übernimmt alle Operationen mit einem einzigen Shift-Out
+    pub fn commit(self) -> Result<()> {
+        let Transaction { register, data } = self;
+
+        register.commit(data)
+    }
 }
@@ -0,0 +1,243 @@
+//! Virtuelle 74HC595 Kette
+//!
+//! Die `SimulatedChain` ist ein GPIO Backend, das sich wie eine echte Kette aus 74HC595 Shift
+//! Registern verhält. Sie bekommt die selben Flanken an DS, CLOCK (SRCLK), LATCH (RCLK) und !OE
+//! wie die Hardware und bildet daraus den Inhalt der Schiebe- und Speicherregister jedes Chips:
+//!
+//! * bei der steigenden Flanke an CLOCK wird DS nach `QA` des ersten Chips übernommen, alle
+//!   anderen Bits rücken eine Stelle weiter, `QH` eines Chips rückt nach `QA` des nächsten Chips
+//! * bei der steigenden Flanke an LATCH wird das Schieberegister in das Speicherregister übernommen
+//! * solange !OE high ist, sind die Ausgänge hochohmig, also aus
+//!
+//! Fehler wie eine vertauschte Bit Reihenfolge, ein fehlender Latch Puls oder eine falsche Anzahl
+//! Clock Pulse fallen so schon auf dem Entwicklungsrechner auf. Wie bei echter Hardware muss ein Pin
+//! exportiert und als Ausgang konfiguriert sein, bevor er geschaltet werden kann.
+//!
+//! Das Backend ist `Clone`, alle Klone teilen sich die selbe Kette. Das `ShiftRegister` öffnet die
+//! Kette bei `GpioDriver::Simulated` selbst, siehe `ShiftRegister::simulated_chain()`.
+//!
+//! # Examples
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let mut relais = ShiftRegister::new(ShiftRegisterType::RELAIS);
+//! relais.gpio = GpioDriver::Simulated;
+//! let chain = relais.simulated_chain().unwrap();
+//!
+//! relais.set(1).unwrap();
+//! relais.set(9).unwrap();
+//! // Ausgang 1 ist `QA` des ersten Chips, Ausgang 9 `QA` des zweiten Chips
+//! assert_eq!(chain.outputs(), vec![0b1, 0b1]);
+//! assert_eq!(chain.pending_clocks(), 0);
+//!
+//! // Über !OE abgeschaltet bleibt das Speicherregister erhalten, die Ausgänge sind aber aus
+//! relais.disable_outputs().unwrap();
+//! assert_eq!(chain.latches(), vec![0b1, 0b1]);
+//! assert_eq!(chain.outputs(), vec![0, 0]);
+//! relais.enable_outputs().unwrap();
+//! assert_eq!(chain.outputs(), vec![0b1, 0b1]);
+//! ```
+//!
+//! Ein fehlender Latch Puls fällt auf, die Ausgänge behalten den alten Stand.
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let (ds, clock, latch) = (1, 2, 3);
+//! let mut chain = SimulatedChain::new(1, ds, clock, latch, None);
+//! for pin in vec![ds, clock, latch] {
+//!     chain.export(pin).unwrap();
+//!     chain.set_direction(pin, Direction::Out).unwrap();
+//! }
+//!
+//! // Ein Bit high, danach sieben Bits low eintakten
+//! for bit in vec![1, 0, 0, 0, 0, 0, 0, 0] {
+//!     chain.set_value(ds, bit).unwrap();
+//!     chain.set_value(clock, 1).unwrap();
+//!     chain.set_value(clock, 0).unwrap();
+//! }
+//! assert_eq!(chain.shift_registers(), vec![0b1000_0000]);
+//! assert_eq!(chain.outputs(), vec![0]);
+//! assert_eq!(chain.pending_clocks(), 8);
+//!
+//! chain.set_value(latch, 1).unwrap();
+//! assert_eq!(chain.outputs(), vec![0b1000_0000]);
+//! assert_eq!(chain.latch_count(), 1);
+//!
+//! // Nicht als Ausgang konfigurierte Pins lassen sich nicht schalten
+//! assert!(chain.set_value(42, 1).is_err());
+//! ```
+use errors::*;
+use shift_register::gpio_backend::{Direction, GpioBackend};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+
+#[derive(Debug)]
+struct Chain {
+    ds_pin: u64,
+    clock_pin: u64,
+    latch_pin: u64,
+    oe_pin: Option<u64>,
+    /// Exportierte Pins und ihre Richtung
+    directions: HashMap<u64, Direction>,
+    /// Aktueller Pegel aller Pins
+    levels: HashMap<u64, u8>,
+    /// Schieberegister, ein Byte pro Chip, Bit 0 ist `QA`
+    shift: Vec<u8>,
+    /// Speicherregister, ein Byte pro Chip, Bit 0 ist `QA`
+    storage: Vec<u8>,
+    /// Clock Pulse seit dem letzten Latch
+    pending_clocks: usize,
+    /// Anzahl der Latch Pulse
+    latch_count: usize,
+}
+
+impl Chain {
+    fn level(&self, pin: u64) -> u8 {
+        self.levels.get(&pin).cloned().unwrap_or(0)
+    }
+
+    /// Steigende Flanke an SRCLK
+    fn clock(&mut self) {
+        let mut carry = self.level(self.ds_pin);
+        for byte in self.shift.iter_mut() {
+            let out = *byte >> 7;
+            *byte = (*byte << 1) | carry;
+            carry = out;
+        }
+        self.pending_clocks += 1;
+    }
+
+    /// Steigende Flanke an RCLK
+    fn latch(&mut self) {
+        self.storage = self.shift.clone();
+        self.pending_clocks = 0;
+        self.latch_count += 1;
+    }
+}
+
+/// Virtuelle 74HC595 Kette, siehe Modul Dokumentation
+#[derive(Clone, Debug)]
+pub struct SimulatedChain {
+    chain: Arc<Mutex<Chain>>,
+}
+
+impl SimulatedChain {
+    /// Erzeugt eine Kette aus `chips` Shift Registern, alle Register sind leer
+    ///
+    /// # Arguments
+    /// * `chips`       - Anzahl der hintereinander geschalteten Shift Register
+    /// * `ds_pin`      - Daten Pin (`SER` des ersten Chips)
+    /// * `clock_pin`   - Clock Pin (`SRCLK` aller Chips)
+    /// * `latch_pin`   - Latch Pin (`RCLK` aller Chips)
+    /// * `oe_pin`      - !OE Pin aller Chips, ohne Pin liegt !OE fest auf low
+    pub fn new(chips: usize, ds_pin: u64, clock_pin: u64, latch_pin: u64, oe_pin: Option<u64>) -> Self {
+        SimulatedChain {
+            chain: Arc::new(Mutex::new(Chain {
+                ds_pin: ds_pin,
+                clock_pin: clock_pin,
+                latch_pin: latch_pin,
+                oe_pin: oe_pin,
+                directions: HashMap::new(),
+                levels: HashMap::new(),
+                shift: vec![0; chips],
+                storage: vec![0; chips],
+                pending_clocks: 0,
+                latch_count: 0,
+            })),
+        }
+    }
+
+    /// Inhalt der Schieberegister, ein Byte pro Chip, Bit 0 ist `QA`
+    pub fn shift_registers(&self) -> Vec<u8> {
+        self.chain.lock().unwrap().shift.clone()
+    }
+
+    /// Inhalt der Speicherregister, ein Byte pro Chip, Bit 0 ist `QA`
+    pub fn latches(&self) -> Vec<u8> {
+        self.chain.lock().unwrap().storage.clone()
+    }
+
+    /// `false` solange !OE high ist
+    pub fn outputs_enabled(&self) -> bool {
+        let chain = self.chain.lock().unwrap();
+        match chain.oe_pin {
+            Some(pin) => chain.level(pin) == 0,
+            None => true,
+        }
+    }
+
+    /// Pegel der Ausgänge `QA` bis `QH`, ein Byte pro Chip, hochohmige Ausgänge sind `0`
+    ///
+    /// Der Aufbau entspricht `ShiftRegister.data`, wenn die Daten korrekt ausgegeben wurden.
+    pub fn outputs(&self) -> Vec<u8> {
+        let latches = self.latches();
+        match self.outputs_enabled() {
+            true => latches,
+            false => vec![0; latches.len()],
+        }
+    }
+
+    /// Clock Pulse seit dem letzten Latch, nach einer vollständigen Ausgabe `0`
+    pub fn pending_clocks(&self) -> usize {
+        self.chain.lock().unwrap().pending_clocks
+    }
+
+    /// Anzahl der bisherigen Latch Pulse
+    pub fn latch_count(&self) -> usize {
+        self.chain.lock().unwrap().latch_count
+    }
+}
+
+impl GpioBackend for SimulatedChain {
+    fn export(&mut self, pin: u64) -> Result<()> {
+        self.chain.lock().unwrap().directions.insert(pin, Direction::In);
+
+        Ok(())
+    }
+
+    fn unexport(&mut self, pin: u64) -> Result<()> {
+        self.chain.lock().unwrap().directions.remove(&pin);
+
+        Ok(())
+    }
+
+    fn set_direction(&mut self, pin: u64, direction: Direction) -> Result<()> {
+        let mut chain = self.chain.lock().unwrap();
+        match chain.directions.get_mut(&pin) {
+            Some(current) => *current = direction,
+            None => bail!("Virtueller Pin {} ist nicht exportiert", pin),
+        }
+
+        Ok(())
+    }
+
+    fn set_value(&mut self, pin: u64, value: u8) -> Result<()> {
+        let mut chain = self.chain.lock().unwrap();
+        if chain.directions.get(&pin) != Some(&Direction::Out) {
+            bail!("Virtueller Pin {} ist nicht als Ausgang konfiguriert", pin);
+        }
+
+        let rising = value != 0 && chain.level(pin) == 0;
+        chain.levels.insert(pin, if value != 0 { 1 } else { 0 });
+        if rising && pin == chain.clock_pin {
+            chain.clock();
+        }
+        if rising && pin == chain.latch_pin {
+            chain.latch();
+        }
+
+        Ok(())
+    }
+
+    fn get_value(&mut self, pin: u64) -> Result<u8> {
+        let chain = self.chain.lock().unwrap();
+        if !chain.directions.contains_key(&pin) {
+            bail!("Virtueller Pin {} ist nicht exportiert", pin);
+        }
+
+        Ok(chain.level(pin))
+    }
+}
@@ -0,0 +1,142 @@
+//! Hardware SPI Treiber für die Shift Register Ketten
+//!
+//! Anstatt jedes Bit einzeln über DS/CLOCK einzutakten, wird das komplette Datenwort in einem
+//! einzigen Transfer über `/dev/spidevX.Y` ausgegeben. MOSI ist dabei mit DS, SCLK mit CLOCK der
+//! Kette verbunden. Latch und !OE werden weiterhin über das `GpioBackend` geschaltet.
+//!
+//! Für Tests ohne SPI Hardware gibt es den `LoopbackBus`, der jeden Transfer aufzeichnet und die
+//! gesendeten Bytes wie ein MOSI/MISO Loopback zurückliefert.
+//!
+//! # Examples
+//!
+//! ```
+//! use xmz_mod_touch_test_tool::shift_register::*;
+//!
+//! let gpio = RecordingBackend::new();
+//! let spi = LoopbackBus::new();
+//! let mut relais = ShiftRegister::with_backend(ShiftRegisterType::RELAIS, Box::new(gpio.clone()));
+//! relais.set_spi_bus(Box::new(spi.clone()));
+//!
+//! relais.set(1).unwrap();
+//! relais.set(9).unwrap();
+//! // Das Byte des letzten Shift Registers wird zuerst gesendet
+//! assert_eq!(spi.frames(), vec![vec![0, 0b1], vec![0b1, 0b1]]);
+//! // Nach jedem Transfer wird einmal gelatched
+//! assert_eq!(gpio.values(relais.latch_pin.unwrap()), vec![0, 1, 0, 1, 0]);
+//! // DS und CLOCK gehören dem SPI Controller und werden nicht angefasst
+//! assert!(gpio.values(relais.ds_pin.unwrap()).is_empty());
+//! ```
+use errors::*;
+use spidev::{Spidev, SpidevOptions, SpidevTransfer, SpiModeFlags};
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+
+/// Ausgabe Treiber eines Shift Registers
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum OutputDriver {
+    /// Jedes Bit wird über die DS und CLOCK Pins des `GpioBackend` eingetaktet
+    BitBang,
+    /// Das Datenwort wird über `/dev/spidevX.Y` in einem Transfer ausgegeben
+    Spi {
+        device: String,
+        speed_hz: u32,
+        /// Empfangene Bytes müssen den gesendeten entsprechen (MOSI/MISO Brücke)
+        #[serde(default)]
+        loopback: bool,
+    },
+}
+
+impl Default for OutputDriver {
+    fn default() -> Self {
+        OutputDriver::BitBang
+    }
+}
+
+/// Schnittstelle zu einem SPI Bus
+pub trait SpiBus: fmt::Debug + Send {
+    /// Sendet die Bytes (MSB first), Rückgabe sind die gleichzeitig empfangenen Bytes
+    fn transfer(&mut self, tx: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// SPI Bus über das spidev Interface des Linux Kernels
+#[derive(Debug)]
+pub struct SpidevBus {
+    spi: Spidev,
+    loopback: bool,
+}
+
+impl SpidevBus {
+    /// Öffnet und konfiguriert das spidev Device (SPI Mode 0, 8 Bit, MSB first)
+    ///
+    /// # Arguments
+    /// * `path`        - Pfad des Devices, z.B. `/dev/spidev1.0`
+    /// * `speed_hz`    - Maximale Taktfrequenz
+    pub fn open<P: AsRef<Path>>(path: P, speed_hz: u32) -> Result<Self> {
+        let mut spi = try!(Spidev::open(path));
+        let options = SpidevOptions::new()
+            .bits_per_word(8)
+            .max_speed_hz(speed_hz)
+            .mode(SpiModeFlags::SPI_MODE_0)
+            .build();
+        try!(spi.configure(&options));
+
+        Ok(SpidevBus {
+            spi: spi,
+            loopback: false,
+        })
+    }
+
+    /// Schaltet den Loopback Test Modus ein oder aus
+    ///
+    /// Im Loopback Modus muss jeder Transfer die gesendeten Bytes wieder empfangen, ansonsten
+    /// liefert `transfer()` einen `ErrorKind::SpiLoopback` Fehler. Dafür muss MOSI mit MISO
+    /// verbunden sein.
+    pub fn set_loopback(&mut self, loopback: bool) {
+        self.loopback = loopback;
+    }
+}
+
+impl SpiBus for SpidevBus {
+    fn transfer(&mut self, tx: &[u8]) -> Result<Vec<u8>> {
+        let mut rx = vec![0; tx.len()];
+        {
+            let mut transfer = SpidevTransfer::read_write(tx, &mut rx);
+            try!(self.spi.transfer(&mut transfer));
+        }
+        if self.loopback && rx != tx {
+            bail!(ErrorKind::SpiLoopback(tx.to_vec(), rx));
+        }
+
+        Ok(rx)
+    }
+}
+
+/// Userspace Ersatz für ein spidev Device mit MOSI/MISO Loopback
+///
+/// Alle Klone teilen sich die selbe Aufzeichnung der Transfers.
+#[derive(Clone, Debug, Default)]
+pub struct LoopbackBus {
+    frames: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl LoopbackBus {
+    pub fn new() -> Self {
+        LoopbackBus::default()
+    }
+
+    /// Alle bisher gesendeten Transfers
+    pub fn frames(&self) -> Vec<Vec<u8>> {
+        self.frames.lock().unwrap().clone()
+    }
+}
+
+impl SpiBus for LoopbackBus {
+    fn transfer(&mut self, tx: &[u8]) -> Result<Vec<u8>> {
+        self.frames.lock().unwrap().push(tx.to_vec());
+
+        Ok(tx.to_vec())
+    }
+}
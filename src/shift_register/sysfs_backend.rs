@@ -0,0 +1,85 @@
+//! GPIO Backend über das sysfs Interface des Linux Kernels (`/sys/class/gpio`)
+//!
+//! Die `value` Dateien der Pins bleiben nach dem ersten Zugriff geöffnet, so muss nicht für jede
+//! Flanke eine Datei geöffnet werden.
+use errors::*;
+use shift_register::gpio_backend::{Direction, GpioBackend};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use sysfs_gpio::{self, Pin};
+
+
+/// Spricht die Pins über ihre globale Kernel Nummer im sysfs an
+#[derive(Debug, Default)]
+pub struct SysfsBackend {
+    /// Geöffnete `/sys/class/gpio/gpioN/value` Dateien
+    values: HashMap<u64, File>,
+}
+
+impl SysfsBackend {
+    pub fn new() -> Self {
+        SysfsBackend::default()
+    }
+
+    /// Liefert die geöffnete `value` Datei des Pins, beim ersten Zugriff wird sie geöffnet
+    fn value_file(&mut self, pin: u64) -> Result<&mut File> {
+        if !self.values.contains_key(&pin) {
+            let file = try!(OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(format!("/sys/class/gpio/gpio{}/value", pin)));
+            self.values.insert(pin, file);
+        }
+
+        Ok(self.values.get_mut(&pin).expect("value Datei wurde gerade geöffnet"))
+    }
+}
+
+impl GpioBackend for SysfsBackend {
+    fn export(&mut self, pin: u64) -> Result<()> {
+        try!(Pin::new(pin).export());
+
+        Ok(())
+    }
+
+    fn unexport(&mut self, pin: u64) -> Result<()> {
+        self.values.remove(&pin);
+        try!(Pin::new(pin).unexport());
+
+        Ok(())
+    }
+
+    fn set_direction(&mut self, pin: u64, direction: Direction) -> Result<()> {
+        let direction = match direction {
+            Direction::In => sysfs_gpio::Direction::In,
+            Direction::Out => sysfs_gpio::Direction::Out,
+        };
+        try!(Pin::new(pin).set_direction(direction));
+
+        Ok(())
+    }
+
+    fn set_value(&mut self, pin: u64, value: u8) -> Result<()> {
+        let file = try!(self.value_file(pin));
+        try!(file.seek(SeekFrom::Start(0)));
+        try!(file.write_all(match value {
+            0 => b"0",
+            _ => b"1",
+        }));
+
+        Ok(())
+    }
+
+    fn get_value(&mut self, pin: u64) -> Result<u8> {
+        let file = try!(self.value_file(pin));
+        let mut value = String::new();
+        try!(file.seek(SeekFrom::Start(0)));
+        try!(file.read_to_string(&mut value));
+
+        match value.trim() {
+            "0" => Ok(0),
+            _ => Ok(1),
+        }
+    }
+}